@@ -0,0 +1,59 @@
+//! Times sentence segmentation over a synthetic book, or over the Markdown files given
+//! as arguments.
+//!
+//! ```text
+//! cargo run --release --example segment_bench [FILE]...
+//! ```
+use std::time::{Duration, Instant};
+
+/// A chapter of long paragraphs, each nesting emphasis a few levels deep around many
+/// sentences, which is the worst case for segmentation.
+fn synthetic_chapter() -> String {
+    let sentence = "This sentence has `code`, a [link](https://example.com) and more words. ";
+    let mut chapter = String::from("# Chapter\n\n");
+    for _ in 0..5 {
+        let mut paragraph = String::new();
+        for depth in 0..8 {
+            paragraph += &sentence.repeat(1000);
+            paragraph += if depth % 2 == 0 { "*" } else { "**" };
+        }
+        paragraph += &sentence.repeat(1000);
+        for depth in (0..8).rev() {
+            paragraph = paragraph.trim_end().to_string();
+            paragraph += if depth % 2 == 0 { "* " } else { "** " };
+            paragraph += &sentence.repeat(1000);
+        }
+        chapter += paragraph.trim_end();
+        chapter += "\n\n";
+    }
+    chapter
+}
+
+fn segment(text: &str) -> Duration {
+    let mut doc =
+        mdtranslation::from_tokens::cmark_ast_from_tokens(pulldown_cmark::Parser::new(text), text)
+            .expect("markdown is always valid");
+    let start = Instant::now();
+    doc.perform_sentence_segment();
+    start.elapsed()
+}
+
+fn main() {
+    let files: Vec<String> = std::env::args().skip(1).collect();
+    let chapters: Vec<String> = if files.is_empty() {
+        (0..2).map(|_| synthetic_chapter()).collect()
+    } else {
+        files
+            .iter()
+            .map(|path| std::fs::read_to_string(path).expect("cannot read input"))
+            .collect()
+    };
+    let bytes: usize = chapters.iter().map(String::len).sum();
+    let elapsed: Duration = chapters.iter().map(|chapter| segment(chapter)).sum();
+    println!(
+        "segmented {} chapters, {} KiB, in {:.1} ms",
+        chapters.len(),
+        bytes / 1024,
+        elapsed.as_secs_f64() * 1000.0
+    );
+}
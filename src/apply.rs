@@ -0,0 +1,444 @@
+use crate::ast;
+use crate::clause;
+use crate::into_tokens::EventIter;
+use crate::notranslate::{is_untranslatable_block, restore_untranslatable};
+use crate::visit::{walk_block, walk_block_mut, walk_outofband, Visitor, VisitorMut};
+use core::fmt;
+use core::mem;
+use std::collections::HashMap;
+use thiserror::Error;
+
+#[derive(Clone, Error, Debug)]
+pub enum SpliceError {
+    #[error("format error: {0}")]
+    Fmt(#[from] fmt::Error),
+    #[error("translated sentences overlap at {}:{}", .0.line, .0.column)]
+    Overlap(ast::LineColumn),
+}
+
+type TranslationMap<'a, 'b> = HashMap<usize, &'b [ast::InlineNode<'a>]>;
+
+/// A piece of the source to replace with a translation.
+struct Splice<'a, 'b> {
+    span: ast::SourceSpan,
+    /// Where the leaf holding the piece starts, which its continuation lines are
+    /// indented after.
+    leaf_start: usize,
+    source: &'b [ast::InlineNode<'a>],
+    translation: ast::InlineNodeList<'a>,
+}
+
+type SpliceList<'a, 'b> = Vec<Splice<'a, 'b>>;
+
+fn is_sentence_tag(tag: &ast::SurroundingInlineTag<'_>) -> bool {
+    *tag == ast::SurroundingInlineTag::Custom(crate::sentence_segment::SENTENCE_TAG)
+}
+
+/// Returns the whitespace or line break a sentence ends with, which separates it from
+/// the next one and should survive translation.
+fn trailing_separator<'a>(contents: &ast::InlineNodeList<'a>) -> Option<ast::InlineNode<'a>> {
+    match contents.last()? {
+        node @ ast::InlineNode::Content {
+            tag: ast::ContentInlineTag::SoftBreak,
+            ..
+        }
+        | node @ ast::InlineNode::Content {
+            tag: ast::ContentInlineTag::HardBreak,
+            ..
+        } => Some(node.clone()),
+        ast::InlineNode::Content {
+            tag: ast::ContentInlineTag::Text(s),
+            ..
+        } => {
+            let trimmed_len = s.trim_end().len();
+            if trimmed_len < s.len() {
+                Some(ast::InlineNode::Content {
+                    tag: ast::ContentInlineTag::Text(s[trimmed_len..].to_string().into()),
+                    span: Default::default(),
+                })
+            } else {
+                None
+            }
+        }
+        _ => None,
+    }
+}
+
+fn apply_translations_for_leaf_contents<'a>(
+    leaf_contents: &mut ast::InlineNodeList<'a>,
+    translations: &TranslationMap<'a, '_>,
+    idx: &mut usize,
+) {
+    for node in leaf_contents.iter_mut() {
+        if let ast::InlineNode::Surrounding { tag, contents, .. } = node {
+            if is_sentence_tag(tag) {
+                let cur_idx = *idx;
+                *idx += 1;
+                if let Some(translation) = translations.get(&cur_idx) {
+                    let separator = trailing_separator(contents);
+                    let source = mem::replace(contents, translation.to_vec());
+                    restore_untranslatable(&source, contents);
+                    if trailing_separator(contents).is_none() {
+                        contents.extend(separator);
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Replaces the contents of each translated sentence, counting sentences the way clauses
+/// are extracted.
+struct TranslationApplier<'t, 'a, 'b> {
+    translations: &'t TranslationMap<'a, 'b>,
+    idx: usize,
+}
+
+impl<'a> VisitorMut<'a> for TranslationApplier<'_, 'a, '_> {
+    fn visit_block_mut(&mut self, block: &mut ast::BlockNode<'a>) {
+        if !is_untranslatable_block(block) {
+            walk_block_mut(self, block);
+        }
+    }
+
+    // only reached for the contents of leaves, as sentences do not nest
+    fn visit_inlines_mut(&mut self, inlines: &mut ast::InlineNodeList<'a>) {
+        apply_translations_for_leaf_contents(inlines, self.translations, &mut self.idx);
+    }
+}
+
+/// Whether `inlines` hold a piece of emphasis split at a sentence boundary, which shares
+/// its delimiters with the piece in the sentence next to it.
+fn has_split_markup(inlines: &ast::InlineNodeList<'_>, source: &str) -> bool {
+    let is_delimiter = |c: char| matches!(c, '*' | '_' | '~');
+    inlines.iter().any(|node| match node {
+        ast::InlineNode::Surrounding {
+            tag,
+            contents,
+            span,
+        } => {
+            let is_split = match tag {
+                ast::SurroundingInlineTag::Emphasis
+                | ast::SurroundingInlineTag::Strong
+                | ast::SurroundingInlineTag::Strikethrough
+                    if span.is_known() =>
+                {
+                    let text = source.get(span.range.clone()).unwrap_or_default();
+                    !(text.starts_with(is_delimiter) && text.ends_with(is_delimiter))
+                }
+                _ => false,
+            };
+            is_split || has_split_markup(contents, source)
+        }
+        ast::InlineNode::Content { .. } => false,
+    })
+}
+
+fn collect_splices_for_leaf_contents<'a, 'd>(
+    leaf_contents: &'d ast::InlineNodeList<'a>,
+    leaf_span: &ast::SourceSpan,
+    source: &str,
+    translations: &TranslationMap<'a, '_>,
+    idx: &mut usize,
+    splices: &mut SpliceList<'a, 'd>,
+) {
+    let sentence_count = leaf_contents
+        .iter()
+        .filter(
+            |node| matches!(node, ast::InlineNode::Surrounding { tag, .. } if is_sentence_tag(tag)),
+        )
+        .count();
+    let is_translated = (*idx..*idx + sentence_count).any(|idx| translations.contains_key(&idx));
+    if is_translated && leaf_span.is_known() && has_split_markup(leaf_contents, source) {
+        // a translated sentence cannot replace its piece of the shared emphasis, so the
+        // whole leaf is rendered again
+        let mut translated = leaf_contents.clone();
+        apply_translations_for_leaf_contents(&mut translated, translations, idx);
+        splices.push(Splice {
+            span: leaf_span.clone(),
+            leaf_start: leaf_span.range.start,
+            source: leaf_contents,
+            translation: translated,
+        });
+        return;
+    }
+    for node in leaf_contents {
+        if let ast::InlineNode::Surrounding {
+            tag,
+            contents,
+            span,
+        } = node
+        {
+            if is_sentence_tag(tag) {
+                let cur_idx = *idx;
+                *idx += 1;
+                if let Some(translation) = translations.get(&cur_idx) {
+                    if span.is_known() && leaf_span.is_known() {
+                        let mut translation = translation.to_vec();
+                        restore_untranslatable(contents, &mut translation);
+                        splices.push(Splice {
+                            span: span.clone(),
+                            leaf_start: leaf_span.range.start,
+                            source: contents,
+                            translation,
+                        });
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Collects the splices of each translated sentence, counting sentences the way clauses
+/// are extracted.
+struct SpliceCollector<'s, 't, 'd, 'a, 'b> {
+    source: &'s str,
+    translations: &'t TranslationMap<'a, 'b>,
+    idx: usize,
+    splices: SpliceList<'a, 'd>,
+}
+
+impl<'d, 'a> Visitor<'d, 'a> for SpliceCollector<'_, '_, 'd, 'a, '_> {
+    fn visit_block(&mut self, block: &'d ast::BlockNode<'a>) {
+        match block {
+            block if is_untranslatable_block(block) => {}
+            ast::BlockNode::Leaf { contents, span, .. } => collect_splices_for_leaf_contents(
+                contents,
+                span,
+                self.source,
+                self.translations,
+                &mut self.idx,
+                &mut self.splices,
+            ),
+            block => walk_block(self, block),
+        }
+    }
+
+    fn visit_outofband(&mut self, oob: &'d ast::OutOfBandNode<'a>) {
+        match oob {
+            ast::OutOfBandNode::OutOfBandLeaf { contents, span, .. } => {
+                collect_splices_for_leaf_contents(
+                    contents,
+                    span,
+                    self.source,
+                    self.translations,
+                    &mut self.idx,
+                    &mut self.splices,
+                )
+            }
+            oob => walk_outofband(self, oob),
+        }
+    }
+}
+
+/// Renders `inlines` to be spliced into the source.
+///
+/// `&` is written as an entity, since the text of an entity in the source, e.g. `&lt;`,
+/// would otherwise be read back as a different entity or as markup.
+fn render_splice(inlines: ast::InlineNodeList<'_>) -> Result<String, fmt::Error> {
+    use pulldown_cmark::Event;
+
+    let events = EventIter::from_inlines(inlines).map(|event| match event {
+        Event::Text(text) if text.contains('&') => Event::Text(text.replace('&', "&amp;").into()),
+        event => event,
+    });
+    let mut output = String::new();
+    let _ = pulldown_cmark_to_cmark::cmark(events, &mut output, None)?;
+    Ok(output)
+}
+
+/// The prefix of each continuation line of the leaf starting at `leaf_start`, made of the
+/// markers of the blockquotes around it and of spaces for the list items around it.
+fn continuation_prefix(source: &str, leaf_start: usize) -> String {
+    let line_start = source[..leaf_start].rfind('\n').map_or(0, |pos| pos + 1);
+    source[line_start..leaf_start]
+        .chars()
+        .map(|c| if c == '>' || c == '\t' { c } else { ' ' })
+        .collect()
+}
+
+impl<'a> ast::Document<'a> {
+    /// Replaces the contents of every sentence that has a translation into `language`.
+    ///
+    /// The document must have been segmented the same way as when `clause_list` was extracted.
+    pub fn apply_translations(
+        &mut self,
+        clause_list: &clause::DocumentClauseList<'a>,
+        language: &str,
+    ) {
+        let translations = clause_list.translations_into(language);
+        self.accept_mut(&mut TranslationApplier {
+            translations: &translations,
+            idx: 1,
+        });
+    }
+
+    /// Writes `source` with the text of every translated sentence replaced in place.
+    ///
+    /// Unlike rendering the translated document, everything outside of the translated
+    /// sentences is kept byte-for-byte, so the result diffs cleanly against `source`.
+    /// `source` must be the text this document was parsed from.
+    pub fn splice_translations(
+        &self,
+        source: &str,
+        clause_list: &clause::DocumentClauseList<'a>,
+        language: &str,
+    ) -> Result<String, SpliceError> {
+        let translations = clause_list.translations_into(language);
+        let mut collector = SpliceCollector {
+            source,
+            translations: &translations,
+            idx: 1,
+            splices: Vec::new(),
+        };
+        self.accept(&mut collector);
+        let mut splices = collector.splices;
+        splices.sort_by_key(|splice| splice.span.range.start);
+
+        let mut output = String::with_capacity(source.len());
+        let mut last_end = 0;
+        for splice in splices {
+            let range = splice.span.range.clone();
+            if range.start < last_end {
+                return Err(SpliceError::Overlap(splice.span.start));
+            }
+            let original = match source.get(range.clone()) {
+                Some(original) if !original.trim().is_empty() => original,
+                _ => continue,
+            };
+            let translated_text = render_splice(splice.translation)?;
+            if translated_text.trim() == render_splice(splice.source.to_vec())?.trim() {
+                // an untranslated sentence keeps its source text as is
+                continue;
+            }
+            // keep the whitespace around the sentence as it was in the source
+            let leading_len = original.len() - original.trim_start().len();
+            let trailing_len = original.len() - original.trim_end().len();
+            let line_break = format!("\n{}", continuation_prefix(source, splice.leaf_start));
+            output += &source[last_end..range.start + leading_len];
+            output += &translated_text.trim().replace('\n', &line_break);
+            output += &source[range.end - trailing_len..range.end];
+            last_end = range.end;
+        }
+        output += &source[last_end..];
+        Ok(output)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{segmented, translate};
+
+    /// Translates clause `id` of `source` into `translation` and splices it.
+    fn splice(source: &str, id: usize, translation: &str) -> String {
+        let doc = segmented(source);
+        let mut clause_list = doc.extract_clause_list(&"en-US".into());
+        translate(&mut clause_list, id, "zh-CN", translation);
+        doc.splice_translations(source, &clause_list, "zh-CN")
+            .unwrap()
+    }
+
+    #[test]
+    fn splice_replaces_the_translated_sentence_only() {
+        assert_eq!(
+            splice("First one. Second *one*.\n", 1, "第一。"),
+            "第一。 Second *one*.\n"
+        );
+    }
+
+    #[test]
+    fn splice_keeps_emphasis_around_sentences() {
+        assert_eq!(
+            splice(
+                "- **Bold start.** **Bold end.** tail.\n",
+                2,
+                "**粗体结束。** 尾巴。"
+            ),
+            "- **Bold start.** **粗体结束。** 尾巴。\n"
+        );
+    }
+
+    #[test]
+    fn splice_keeps_emphasis_split_between_sentences() {
+        assert_eq!(
+            splice(
+                "- **Bold start. Bold end.** tail.\n",
+                2,
+                "**粗体结束。** 尾巴。"
+            ),
+            "- **Bold start.** **粗体结束。** 尾巴。\n"
+        );
+    }
+
+    #[test]
+    fn splice_indents_continuation_lines() {
+        assert_eq!(
+            splice("- a. B.\n  continued. C.\n", 2, "乙\n继续。"),
+            "- a. 乙\n  继续。 C.\n"
+        );
+        assert_eq!(
+            splice("> - a. B.\n>   continued.\n", 2, "乙\n继续。"),
+            "> - a. 乙\n>   继续。\n"
+        );
+    }
+
+    #[test]
+    fn splice_writes_ampersands_as_entities() {
+        assert_eq!(
+            splice("A &amp; b. Next.\n", 1, "甲 &amp;lt; 乙。"),
+            "甲 &amp;lt; 乙。 Next.\n"
+        );
+    }
+
+    #[test]
+    fn splicing_sentences_into_themselves_changes_nothing() {
+        for source in [
+            "- a. B.\n  continued. C.\n",
+            "A &amp; b. Next \\* one.\n",
+            "> _Quoted_ &lt;text&gt;.\n> More.\n",
+        ] {
+            let doc = segmented(source);
+            let mut clause_list = doc.extract_clause_list(&"en-US".into());
+            for clause in &mut clause_list.clauses {
+                let contents = clause.clause_contents.clone();
+                clause
+                    .clause_translations
+                    .push(("fr".into(), contents.to_vec()));
+            }
+            assert_eq!(
+                doc.splice_translations(source, &clause_list, "fr").unwrap(),
+                source
+            );
+        }
+    }
+
+    #[test]
+    fn overlapping_splices_are_reported() {
+        let source = "One. Two.\n";
+        let mut doc = segmented(source);
+        let mut clause_list = doc.extract_clause_list(&"en-US".into()).into_owned();
+        translate(&mut clause_list, 1, "fr", "Un.");
+        translate(&mut clause_list, 2, "fr", "Deux.");
+        if let ast::BlockNode::Leaf { contents, .. } = &mut doc.blocks[0] {
+            if let ast::InlineNode::Surrounding { span, .. } = &mut contents[1] {
+                span.range.start = 2;
+            }
+        }
+        assert!(matches!(
+            doc.splice_translations(source, &clause_list, "fr"),
+            Err(SpliceError::Overlap(_))
+        ));
+    }
+
+    #[test]
+    fn apply_keeps_the_separator_between_sentences() {
+        let mut doc = segmented("First one. Second one.\n");
+        let mut clause_list = doc.extract_clause_list(&"en-US".into()).into_owned();
+        translate(&mut clause_list, 1, "fr", "Premier.");
+        doc.apply_translations(&clause_list, "fr");
+        let mut output = String::new();
+        pulldown_cmark_to_cmark::cmark(doc.into_tokens(), &mut output, None).unwrap();
+        assert_eq!(output, "Premier. Second one.");
+    }
+}
@@ -1,111 +1,470 @@
+use crate::tag::cowstr_into_static;
+use core::ops::Range;
 use pulldown_cmark::CowStr;
 pub use pulldown_cmark::{Alignment, CodeBlockKind, LinkType};
+
+/// A 1-based line and column (counted in chars) within the source text.
+///
+/// The default value, with line 0, denotes an unknown position.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct LineColumn {
+    pub line: usize,
+    pub column: usize,
+}
+
+impl LineColumn {
+    pub fn is_known(&self) -> bool {
+        self.line != 0
+    }
+
+    pub(crate) fn advance(self, text: &str) -> LineColumn {
+        let mut result = self;
+        for ch in text.chars() {
+            if ch == '\n' {
+                result.line += 1;
+                result.column = 1;
+            } else {
+                result.column += 1;
+            }
+        }
+        result
+    }
+}
+
+/// The region of the source text a node was parsed from.
+///
+/// Nodes that are synthesized rather than parsed carry the default span.
+#[derive(Clone, PartialEq, Eq, Debug, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SourceSpan {
+    pub range: Range<usize>,
+    pub start: LineColumn,
+    pub end: LineColumn,
+}
+
+impl SourceSpan {
+    pub fn is_known(&self) -> bool {
+        self.start.is_known()
+    }
+
+    /// Returns the smallest span covering both `self` and `other`.
+    pub fn cover(&self, other: &SourceSpan) -> SourceSpan {
+        if !other.is_known() {
+            return self.clone();
+        }
+        if !self.is_known() {
+            return other.clone();
+        }
+        let (start, range_start) = if self.range.start <= other.range.start {
+            (self.start, self.range.start)
+        } else {
+            (other.start, other.range.start)
+        };
+        let (end, range_end) = if self.range.end >= other.range.end {
+            (self.end, self.range.end)
+        } else {
+            (other.end, other.range.end)
+        };
+        SourceSpan {
+            range: range_start..range_end,
+            start,
+            end,
+        }
+    }
+
+    pub(crate) fn covering_inlines(inlines: &[InlineNode<'_>]) -> SourceSpan {
+        inlines
+            .iter()
+            .fold(SourceSpan::default(), |span, node| span.cover(node.span()))
+    }
+}
 #[derive(Clone, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum ContainerBlockTag<'a> {
     BlockQuote,
     List(Option<u64>),
     ListItem,
-    Table(Vec<Alignment>),
+    Table(
+        #[cfg_attr(feature = "serde", serde(with = "crate::serde_support::alignments"))]
+        Vec<Alignment>,
+    ),
     TableHead,
     TableRow,
-    Custom(CowStr<'a>),
+    Custom(#[cfg_attr(feature = "serde", serde(with = "crate::serde_support::cowstr"))] CowStr<'a>),
+}
+
+impl<'a> ContainerBlockTag<'a> {
+    pub fn into_owned(self) -> ContainerBlockTag<'static> {
+        match self {
+            ContainerBlockTag::BlockQuote => ContainerBlockTag::BlockQuote,
+            ContainerBlockTag::List(start) => ContainerBlockTag::List(start),
+            ContainerBlockTag::ListItem => ContainerBlockTag::ListItem,
+            ContainerBlockTag::Table(alignments) => ContainerBlockTag::Table(alignments),
+            ContainerBlockTag::TableHead => ContainerBlockTag::TableHead,
+            ContainerBlockTag::TableRow => ContainerBlockTag::TableRow,
+            ContainerBlockTag::Custom(s) => ContainerBlockTag::Custom(cowstr_into_static(s)),
+        }
+    }
 }
 
 #[derive(Clone, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum LeafBlockTag<'a> {
     Paragraph,
     Heading(u32),
-    CodeBlock(CodeBlockKind<'a>),
+    CodeBlock(
+        #[cfg_attr(
+            feature = "serde",
+            serde(with = "crate::serde_support::CodeBlockKindDef")
+        )]
+        CodeBlockKind<'a>,
+    ),
     TableCell,
     Html,
-    Custom(CowStr<'a>),
+    Custom(#[cfg_attr(feature = "serde", serde(with = "crate::serde_support::cowstr"))] CowStr<'a>),
+}
+
+impl<'a> LeafBlockTag<'a> {
+    pub fn into_owned(self) -> LeafBlockTag<'static> {
+        match self {
+            LeafBlockTag::Paragraph => LeafBlockTag::Paragraph,
+            LeafBlockTag::Heading(level) => LeafBlockTag::Heading(level),
+            LeafBlockTag::CodeBlock(CodeBlockKind::Indented) => {
+                LeafBlockTag::CodeBlock(CodeBlockKind::Indented)
+            }
+            LeafBlockTag::CodeBlock(CodeBlockKind::Fenced(s)) => {
+                LeafBlockTag::CodeBlock(CodeBlockKind::Fenced(cowstr_into_static(s)))
+            }
+            LeafBlockTag::TableCell => LeafBlockTag::TableCell,
+            LeafBlockTag::Html => LeafBlockTag::Html,
+            LeafBlockTag::Custom(s) => LeafBlockTag::Custom(cowstr_into_static(s)),
+        }
+    }
 }
 
 #[derive(Clone, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum MarkupBlockTag<'a> {
     Rule,
-    Custom(CowStr<'a>),
+    Custom(#[cfg_attr(feature = "serde", serde(with = "crate::serde_support::cowstr"))] CowStr<'a>),
+}
+
+impl<'a> MarkupBlockTag<'a> {
+    pub fn into_owned(self) -> MarkupBlockTag<'static> {
+        match self {
+            MarkupBlockTag::Rule => MarkupBlockTag::Rule,
+            MarkupBlockTag::Custom(s) => MarkupBlockTag::Custom(cowstr_into_static(s)),
+        }
+    }
 }
 
 #[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum BlockNode<'a> {
     Container {
         tag: ContainerBlockTag<'a>,
         children: BlockNodeList<'a>,
+        span: SourceSpan,
     },
     Leaf {
         tag: LeafBlockTag<'a>,
         contents: Vec<InlineNode<'a>>,
+        span: SourceSpan,
     },
     Markup {
         tag: MarkupBlockTag<'a>,
+        span: SourceSpan,
     },
 }
 
+impl<'a> BlockNode<'a> {
+    /// Copies the borrowed strings of this node, detaching it from the source text.
+    pub fn into_owned(self) -> BlockNode<'static> {
+        match self {
+            BlockNode::Container {
+                tag,
+                children,
+                span,
+            } => BlockNode::Container {
+                tag: tag.into_owned(),
+                children: children.into_iter().map(BlockNode::into_owned).collect(),
+                span,
+            },
+            BlockNode::Leaf {
+                tag,
+                contents,
+                span,
+            } => BlockNode::Leaf {
+                tag: tag.into_owned(),
+                contents: contents.into_iter().map(InlineNode::into_owned).collect(),
+                span,
+            },
+            BlockNode::Markup { tag, span } => BlockNode::Markup {
+                tag: tag.into_owned(),
+                span,
+            },
+        }
+    }
+
+    pub fn span(&self) -> &SourceSpan {
+        match self {
+            BlockNode::Container { span, .. }
+            | BlockNode::Leaf { span, .. }
+            | BlockNode::Markup { span, .. } => span,
+        }
+    }
+}
+
 pub type BlockNodeList<'a> = Vec<BlockNode<'a>>;
 
 #[derive(Clone, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum OutOfBandContainerTag<'a> {
-    FootNoteDef(CowStr<'a>),
-    Custom(CowStr<'a>),
+    FootNoteDef(
+        #[cfg_attr(feature = "serde", serde(with = "crate::serde_support::cowstr"))] CowStr<'a>,
+    ),
+    Custom(#[cfg_attr(feature = "serde", serde(with = "crate::serde_support::cowstr"))] CowStr<'a>),
+}
+
+impl<'a> OutOfBandContainerTag<'a> {
+    pub fn into_owned(self) -> OutOfBandContainerTag<'static> {
+        match self {
+            OutOfBandContainerTag::FootNoteDef(s) => {
+                OutOfBandContainerTag::FootNoteDef(cowstr_into_static(s))
+            }
+            OutOfBandContainerTag::Custom(s) => {
+                OutOfBandContainerTag::Custom(cowstr_into_static(s))
+            }
+        }
+    }
 }
 
 #[derive(Clone, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum OutOfBandLeafTag<'a> {
-    Custom(CowStr<'a>),
+    Custom(#[cfg_attr(feature = "serde", serde(with = "crate::serde_support::cowstr"))] CowStr<'a>),
+}
+
+impl<'a> OutOfBandLeafTag<'a> {
+    pub fn into_owned(self) -> OutOfBandLeafTag<'static> {
+        match self {
+            OutOfBandLeafTag::Custom(s) => OutOfBandLeafTag::Custom(cowstr_into_static(s)),
+        }
+    }
 }
 
 #[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum OutOfBandNode<'a> {
     OutOfBandContainer {
         tag: OutOfBandContainerTag<'a>,
         children: BlockNodeList<'a>,
+        span: SourceSpan,
     },
     OutOfBandLeaf {
         tag: OutOfBandLeafTag<'a>,
         contents: InlineNodeList<'a>,
+        span: SourceSpan,
     },
 }
 
+impl<'a> OutOfBandNode<'a> {
+    /// Copies the borrowed strings of this node, detaching it from the source text.
+    pub fn into_owned(self) -> OutOfBandNode<'static> {
+        match self {
+            OutOfBandNode::OutOfBandContainer {
+                tag,
+                children,
+                span,
+            } => OutOfBandNode::OutOfBandContainer {
+                tag: tag.into_owned(),
+                children: children.into_iter().map(BlockNode::into_owned).collect(),
+                span,
+            },
+            OutOfBandNode::OutOfBandLeaf {
+                tag,
+                contents,
+                span,
+            } => OutOfBandNode::OutOfBandLeaf {
+                tag: tag.into_owned(),
+                contents: contents.into_iter().map(InlineNode::into_owned).collect(),
+                span,
+            },
+        }
+    }
+
+    pub fn span(&self) -> &SourceSpan {
+        match self {
+            OutOfBandNode::OutOfBandContainer { span, .. }
+            | OutOfBandNode::OutOfBandLeaf { span, .. } => span,
+        }
+    }
+}
+
 pub type OutOfBandNodeList<'a> = Vec<OutOfBandNode<'a>>;
 
 #[derive(Clone, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum SurroundingInlineTag<'a> {
     Emphasis,
     Strong,
     Strikethrough,
-    Link(LinkType, CowStr<'a>, CowStr<'a>),
-    Image(LinkType, CowStr<'a>, CowStr<'a>),
-    Custom(CowStr<'a>),
+    Link(
+        #[cfg_attr(feature = "serde", serde(with = "crate::serde_support::LinkTypeDef"))] LinkType,
+        #[cfg_attr(feature = "serde", serde(with = "crate::serde_support::cowstr"))] CowStr<'a>,
+        #[cfg_attr(feature = "serde", serde(with = "crate::serde_support::cowstr"))] CowStr<'a>,
+    ),
+    Image(
+        #[cfg_attr(feature = "serde", serde(with = "crate::serde_support::LinkTypeDef"))] LinkType,
+        #[cfg_attr(feature = "serde", serde(with = "crate::serde_support::cowstr"))] CowStr<'a>,
+        #[cfg_attr(feature = "serde", serde(with = "crate::serde_support::cowstr"))] CowStr<'a>,
+    ),
+    Custom(#[cfg_attr(feature = "serde", serde(with = "crate::serde_support::cowstr"))] CowStr<'a>),
+}
+
+impl<'a> SurroundingInlineTag<'a> {
+    pub fn into_owned(self) -> SurroundingInlineTag<'static> {
+        match self {
+            SurroundingInlineTag::Emphasis => SurroundingInlineTag::Emphasis,
+            SurroundingInlineTag::Strong => SurroundingInlineTag::Strong,
+            SurroundingInlineTag::Strikethrough => SurroundingInlineTag::Strikethrough,
+            SurroundingInlineTag::Link(link_type, url, title) => SurroundingInlineTag::Link(
+                link_type,
+                cowstr_into_static(url),
+                cowstr_into_static(title),
+            ),
+            SurroundingInlineTag::Image(link_type, url, title) => SurroundingInlineTag::Image(
+                link_type,
+                cowstr_into_static(url),
+                cowstr_into_static(title),
+            ),
+            SurroundingInlineTag::Custom(s) => SurroundingInlineTag::Custom(cowstr_into_static(s)),
+        }
+    }
 }
 
 #[derive(Clone, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum ContentInlineTag<'a> {
-    Text(CowStr<'a>),
-    Code(CowStr<'a>),
-    RawHtml(CowStr<'a>),
-    FootnoteRef(CowStr<'a>),
+    Text(#[cfg_attr(feature = "serde", serde(with = "crate::serde_support::cowstr"))] CowStr<'a>),
+    Code(#[cfg_attr(feature = "serde", serde(with = "crate::serde_support::cowstr"))] CowStr<'a>),
+    RawHtml(
+        #[cfg_attr(feature = "serde", serde(with = "crate::serde_support::cowstr"))] CowStr<'a>,
+    ),
+    FootnoteRef(
+        #[cfg_attr(feature = "serde", serde(with = "crate::serde_support::cowstr"))] CowStr<'a>,
+    ),
     TaskListMarker(bool),
     SoftBreak,
     HardBreak,
-    Custom(CowStr<'a>),
+    Custom(#[cfg_attr(feature = "serde", serde(with = "crate::serde_support::cowstr"))] CowStr<'a>),
+}
+
+impl<'a> ContentInlineTag<'a> {
+    pub fn into_owned(self) -> ContentInlineTag<'static> {
+        match self {
+            ContentInlineTag::Text(s) => ContentInlineTag::Text(cowstr_into_static(s)),
+            ContentInlineTag::Code(s) => ContentInlineTag::Code(cowstr_into_static(s)),
+            ContentInlineTag::RawHtml(s) => ContentInlineTag::RawHtml(cowstr_into_static(s)),
+            ContentInlineTag::FootnoteRef(s) => {
+                ContentInlineTag::FootnoteRef(cowstr_into_static(s))
+            }
+            ContentInlineTag::TaskListMarker(checked) => ContentInlineTag::TaskListMarker(checked),
+            ContentInlineTag::SoftBreak => ContentInlineTag::SoftBreak,
+            ContentInlineTag::HardBreak => ContentInlineTag::HardBreak,
+            ContentInlineTag::Custom(s) => ContentInlineTag::Custom(cowstr_into_static(s)),
+        }
+    }
 }
 
 #[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum InlineNode<'a> {
     Surrounding {
         tag: SurroundingInlineTag<'a>,
         contents: InlineNodeList<'a>,
+        span: SourceSpan,
     },
     Content {
         tag: ContentInlineTag<'a>,
+        span: SourceSpan,
     },
 }
 
+impl<'a> InlineNode<'a> {
+    /// Copies the borrowed strings of this node, detaching it from the source text.
+    pub fn into_owned(self) -> InlineNode<'static> {
+        match self {
+            InlineNode::Surrounding {
+                tag,
+                contents,
+                span,
+            } => InlineNode::Surrounding {
+                tag: tag.into_owned(),
+                contents: contents.into_iter().map(InlineNode::into_owned).collect(),
+                span,
+            },
+            InlineNode::Content { tag, span } => InlineNode::Content {
+                tag: tag.into_owned(),
+                span,
+            },
+        }
+    }
+
+    pub fn span(&self) -> &SourceSpan {
+        match self {
+            InlineNode::Surrounding { span, .. } | InlineNode::Content { span, .. } => span,
+        }
+    }
+}
+
 pub type InlineNodeList<'a> = Vec<InlineNode<'a>>;
 
 #[derive(Default, Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Document<'a> {
     pub(crate) blocks: BlockNodeList<'a>,
     pub(crate) outofbands: OutOfBandNodeList<'a>,
 }
+
+impl<'a> Document<'a> {
+    /// Builds a document out of its top-level blocks and its out-of-band nodes, such as
+    /// footnote definitions.
+    pub fn from_blocks(blocks: BlockNodeList<'a>, outofbands: OutOfBandNodeList<'a>) -> Self {
+        Document { blocks, outofbands }
+    }
+
+    pub fn blocks(&self) -> &BlockNodeList<'a> {
+        &self.blocks
+    }
+
+    pub fn blocks_mut(&mut self) -> &mut BlockNodeList<'a> {
+        &mut self.blocks
+    }
+
+    pub fn outofbands(&self) -> &OutOfBandNodeList<'a> {
+        &self.outofbands
+    }
+
+    pub fn outofbands_mut(&mut self) -> &mut OutOfBandNodeList<'a> {
+        &mut self.outofbands
+    }
+
+    /// Copies the borrowed strings of this document, so that it outlives the source text.
+    pub fn into_owned(self) -> Document<'static> {
+        Document {
+            blocks: self.blocks.into_iter().map(BlockNode::into_owned).collect(),
+            outofbands: self
+                .outofbands
+                .into_iter()
+                .map(OutOfBandNode::into_owned)
+                .collect(),
+        }
+    }
+
+    /// Takes the document apart, the inverse of `from_blocks`.
+    pub fn into_parts(self) -> (BlockNodeList<'a>, OutOfBandNodeList<'a>) {
+        (self.blocks, self.outofbands)
+    }
+}
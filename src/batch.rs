@@ -0,0 +1,538 @@
+use crate::apply::SpliceError;
+use crate::ast;
+use crate::bilingual::BilingualLayout;
+use crate::clause::{ClauseFileError, DocumentClauseList, SentenceIdStyle};
+use crate::from_tokens::{cmark_ast_from_tokens, FromTokensError};
+use crate::glossary::Glossary;
+use crate::into_tokens::{CustomTagRegistry, CustomTagRendering};
+use core::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum BatchError {
+    #[error("{0}: io error: {1}")]
+    IO(PathBuf, #[source] io::Error),
+    #[error("{0}: from tokens error: {1}")]
+    FromTokens(PathBuf, #[source] Box<FromTokensError>),
+    #[error("{0}: format error: {1}")]
+    Fmt(PathBuf, #[source] fmt::Error),
+    #[error("{0}: clause file error: {1}")]
+    ClauseFile(PathBuf, #[source] ClauseFileError),
+    #[error("{0}: splice error: {1}")]
+    Splice(PathBuf, #[source] SpliceError),
+    #[error("`{0}` cannot be combined with `{1}`")]
+    ConflictingOptions(&'static str, &'static str),
+}
+
+fn find_markdown_files_in(
+    root: &Path,
+    relative_dir: &Path,
+    excluded: Option<&Path>,
+    output: &mut Vec<PathBuf>,
+) -> Result<(), BatchError> {
+    let dir = root.join(relative_dir);
+    let read_dir = fs::read_dir(&dir).map_err(|e| BatchError::IO(dir.clone(), e))?;
+    let mut entries = Vec::new();
+    for entry in read_dir {
+        let entry = entry.map_err(|e| BatchError::IO(dir.clone(), e))?;
+        entries.push(entry.file_name());
+    }
+    entries.sort();
+    for name in entries {
+        if name.to_string_lossy().starts_with('.') {
+            continue;
+        }
+        let relative_path = relative_dir.join(&name);
+        let path = root.join(&relative_path);
+        if path.is_dir() {
+            let is_excluded = match (excluded, path.canonicalize()) {
+                (Some(excluded), Ok(path)) => path == excluded,
+                _ => false,
+            };
+            if !is_excluded {
+                find_markdown_files_in(root, &relative_path, excluded, output)?;
+            }
+        } else if path.extension().is_some_and(|ext| ext == "md") {
+            output.push(relative_path);
+        }
+    }
+    Ok(())
+}
+
+/// Lists the Markdown files under `dir` recursively, as paths relative to `dir`.
+///
+/// Hidden entries are skipped, and so is the directory `excluded` (typically the
+/// output directory, when it lives inside `dir`).
+pub fn find_markdown_files(
+    dir: &Path,
+    excluded: Option<&Path>,
+) -> Result<Vec<PathBuf>, BatchError> {
+    let excluded = excluded.and_then(|path| path.canonicalize().ok());
+    let mut output = Vec::new();
+    find_markdown_files_in(dir, Path::new(""), excluded.as_deref(), &mut output)?;
+    Ok(output)
+}
+
+/// Lists the chapters linked from an mdBook `SUMMARY.md`, in order, as paths relative
+/// to the directory containing it.
+pub fn find_summary_chapters(summary_path: &Path) -> Result<Vec<PathBuf>, BatchError> {
+    use pulldown_cmark::{Event, Tag};
+
+    let summary_text =
+        fs::read_to_string(summary_path).map_err(|e| BatchError::IO(summary_path.to_owned(), e))?;
+    let mut output: Vec<PathBuf> = Vec::new();
+    for event in pulldown_cmark::Parser::new(&summary_text) {
+        if let Event::Start(Tag::Link(_, destination, _)) = event {
+            let destination = destination.split('#').next().unwrap_or_default();
+            if destination.is_empty() || destination.contains("://") {
+                continue;
+            }
+            let path = Path::new(destination.trim_start_matches("./")).to_owned();
+            if !output.contains(&path) {
+                output.push(path);
+            }
+        }
+    }
+    Ok(output)
+}
+
+/// A file to process in a batch, along with where its result goes.
+#[derive(Clone, Debug)]
+pub struct BatchItem {
+    pub relative_path: PathBuf,
+    pub input_path: PathBuf,
+    pub output_path: PathBuf,
+}
+
+impl BatchItem {
+    /// Maps each of `relative_paths` under `input_dir` to the same path under `output_dir`.
+    pub fn plan(input_dir: &Path, relative_paths: Vec<PathBuf>, output_dir: &Path) -> Vec<Self> {
+        relative_paths
+            .into_iter()
+            .map(|relative_path| BatchItem {
+                input_path: input_dir.join(&relative_path),
+                output_path: output_dir.join(&relative_path),
+                relative_path,
+            })
+            .collect()
+    }
+
+    /// Whether the output was written after the input was last modified.
+    pub fn is_up_to_date(&self) -> bool {
+        let modified = |path: &Path| fs::metadata(path).and_then(|m| m.modified()).ok();
+        match (modified(&self.input_path), modified(&self.output_path)) {
+            (Some(input_time), Some(output_time)) => output_time >= input_time,
+            _ => false,
+        }
+    }
+
+    fn read_input(&self) -> Result<String, BatchError> {
+        fs::read_to_string(&self.input_path).map_err(|e| BatchError::IO(self.input_path.clone(), e))
+    }
+
+    fn write_output(&self, output_text: &str) -> Result<(), BatchError> {
+        if let Some(parent) = self.output_path.parent() {
+            fs::create_dir_all(parent).map_err(|e| BatchError::IO(parent.to_owned(), e))?;
+        }
+        fs::write(&self.output_path, output_text)
+            .map_err(|e| BatchError::IO(self.output_path.clone(), e))
+    }
+
+    fn extract_error(&self, e: ExtractError) -> BatchError {
+        match e {
+            ExtractError::FromTokens(e) => BatchError::FromTokens(self.input_path.clone(), e),
+            ExtractError::ClauseFromTokens(e) => {
+                BatchError::FromTokens(self.output_path.clone(), e)
+            }
+            ExtractError::ClauseFile(e) => BatchError::ClauseFile(self.output_path.clone(), e),
+            ExtractError::Fmt(e) => BatchError::Fmt(self.output_path.clone(), e),
+        }
+    }
+
+    /// Extracts the clauses of the input file and writes the clause file.
+    pub fn extract(&self, options: &ExtractOptions<'_>) -> Result<(), BatchError> {
+        let input_text = self.read_input()?;
+        let output_text = extract_clause_file(
+            &input_text,
+            &self.relative_path.display().to_string(),
+            options,
+        )
+        .map_err(|e| self.extract_error(e))?;
+        self.write_output(&output_text)
+    }
+
+    /// Like `extract`, but keeps the translations of the clause file being replaced, if
+    /// any.
+    pub fn update(&self, options: &ExtractOptions<'_>) -> Result<(), BatchError> {
+        let input_text = self.read_input()?;
+        let previous_text = match fs::read_to_string(&self.output_path) {
+            Ok(text) => Some(text),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => None,
+            Err(e) => return Err(BatchError::IO(self.output_path.clone(), e)),
+        };
+        let output_text = update_clause_file(
+            &input_text,
+            &self.relative_path.display().to_string(),
+            previous_text.as_deref(),
+            options,
+        )
+        .map_err(|e| self.extract_error(e))?;
+        self.write_output(&output_text)
+    }
+
+    /// Applies the translations of the clause file at the same relative path under
+    /// `clauses_dir` to the input file, and writes the translated file.
+    pub fn apply(&self, clauses_dir: &Path, options: &ApplyOptions) -> Result<(), BatchError> {
+        let clauses_path = clauses_dir.join(&self.relative_path);
+        let input_text = self.read_input()?;
+        let clauses_text = fs::read_to_string(&clauses_path)
+            .map_err(|e| BatchError::IO(clauses_path.clone(), e))?;
+        let output_text =
+            apply_clause_file(&input_text, &clauses_text, options).map_err(|e| match e {
+                ApplyError::FromTokens(e) => BatchError::FromTokens(self.input_path.clone(), e),
+                ApplyError::ClauseFromTokens(e) => BatchError::FromTokens(clauses_path.clone(), e),
+                ApplyError::ClauseFile(e) => BatchError::ClauseFile(clauses_path.clone(), e),
+                ApplyError::Fmt(e) => BatchError::Fmt(self.output_path.clone(), e),
+                ApplyError::Splice(e) => BatchError::Splice(self.input_path.clone(), e),
+                ApplyError::ConflictingOptions(a, b) => BatchError::ConflictingOptions(a, b),
+            })?;
+        self.write_output(&output_text)
+    }
+}
+
+// workers may parse and extract documents and hand them back to the calling thread
+const _: fn() = || {
+    fn assert_send<T: Send>() {}
+    assert_send::<ast::Document<'static>>();
+    assert_send::<DocumentClauseList<'static>>();
+};
+
+/// The number of threads `run_parallel` is usually given, one per available CPU.
+pub fn default_jobs() -> usize {
+    thread::available_parallelism().map_or(1, |jobs| jobs.get())
+}
+
+/// Runs `task` over each of `items` on up to `jobs` threads, and returns the results in
+/// the order of `items`. An item failing does not stop the others.
+pub fn run_parallel<T, F>(items: &[BatchItem], jobs: usize, task: F) -> Vec<Result<T, BatchError>>
+where
+    T: Send,
+    F: Fn(&BatchItem) -> Result<T, BatchError> + Sync,
+{
+    let next_idx = AtomicUsize::new(0);
+    let mut results: Vec<_> = items.iter().map(|_| None).collect();
+    thread::scope(|scope| {
+        let workers: Vec<_> = (0..jobs.clamp(1, items.len().max(1)))
+            .map(|_| {
+                scope.spawn(|| {
+                    let mut done = Vec::new();
+                    loop {
+                        let idx = next_idx.fetch_add(1, Ordering::Relaxed);
+                        match items.get(idx) {
+                            Some(item) => done.push((idx, task(item))),
+                            None => return done,
+                        }
+                    }
+                })
+            })
+            .collect();
+        for worker in workers {
+            let done = worker
+                .join()
+                .unwrap_or_else(|panic| std::panic::resume_unwind(panic));
+            for (idx, result) in done {
+                results[idx] = Some(result);
+            }
+        }
+    });
+    results
+        .into_iter()
+        .map(|result| result.expect("every item is taken by a worker"))
+        .collect()
+}
+
+#[derive(Error, Debug)]
+pub enum ExtractError {
+    #[error("from tokens error: {0}")]
+    FromTokens(#[source] Box<FromTokensError>),
+    #[error("clause file: from tokens error: {0}")]
+    ClauseFromTokens(#[source] Box<FromTokensError>),
+    #[error("clause file error: {0}")]
+    ClauseFile(#[from] ClauseFileError),
+    #[error("format error: {0}")]
+    Fmt(#[from] fmt::Error),
+}
+
+/// How `extract_clause_file` builds clause files.
+#[derive(Clone, Debug)]
+pub struct ExtractOptions<'g> {
+    pub source_language: String,
+    /// The glossary whose terms are listed with each clause.
+    pub glossary: Option<&'g Glossary>,
+    /// The prefix of the comments holding notes for translators.
+    pub note_prefix: String,
+}
+
+impl Default for ExtractOptions<'_> {
+    fn default() -> Self {
+        ExtractOptions {
+            source_language: "en-US".to_string(),
+            glossary: None,
+            note_prefix: crate::notes::DEFAULT_NOTE_PREFIX.to_string(),
+        }
+    }
+}
+
+/// Runs the extraction pipeline over `input_text` and renders the resulting clause file.
+pub fn extract_clause_file(
+    input_text: &str,
+    source_name: &str,
+    options: &ExtractOptions<'_>,
+) -> Result<String, ExtractError> {
+    update_clause_file(input_text, source_name, None, options)
+}
+
+/// Like `extract_clause_file`, but carries the translations of `previous_clauses_text`,
+/// the clause file being replaced, over to the new one.
+pub fn update_clause_file(
+    input_text: &str,
+    source_name: &str,
+    previous_clauses_text: Option<&str>,
+    options: &ExtractOptions<'_>,
+) -> Result<String, ExtractError> {
+    let reader = pulldown_cmark::Parser::new(input_text);
+    let mut ast = cmark_ast_from_tokens(reader, input_text)
+        .map_err(|e| ExtractError::FromTokens(Box::new(e)))?;
+    ast.perform_sentence_segment();
+    let mut clause_list = ast.extract_clause_list(&options.source_language.as_str().into());
+    clause_list.set_source_name(source_name.into());
+    clause_list.attach_notes(&ast, &options.note_prefix);
+    if let Some(glossary) = options.glossary {
+        clause_list.annotate_glossary(glossary);
+    }
+    if let Some(previous_clauses_text) = previous_clauses_text {
+        let previous_reader = pulldown_cmark::Parser::new(previous_clauses_text);
+        let previous_ast = cmark_ast_from_tokens(previous_reader, previous_clauses_text)
+            .map_err(|e| ExtractError::ClauseFromTokens(Box::new(e)))?;
+        clause_list.merge_translations(DocumentClauseList::from_clause_document(previous_ast)?);
+    }
+    let mut output_text = String::new();
+    let _ = pulldown_cmark_to_cmark::cmark(clause_list.into_tokens(), &mut output_text, None)?;
+    Ok(output_text)
+}
+
+#[derive(Error, Debug)]
+pub enum ApplyError {
+    #[error("from tokens error: {0}")]
+    FromTokens(#[source] Box<FromTokensError>),
+    #[error("clause file: from tokens error: {0}")]
+    ClauseFromTokens(#[source] Box<FromTokensError>),
+    #[error("clause file error: {0}")]
+    ClauseFile(#[from] ClauseFileError),
+    #[error("format error: {0}")]
+    Fmt(#[from] fmt::Error),
+    #[error("splice error: {0}")]
+    Splice(#[from] SpliceError),
+    #[error("`{0}` cannot be combined with `{1}`")]
+    ConflictingOptions(&'static str, &'static str),
+}
+
+/// How `apply_clause_file` renders translated documents.
+#[derive(Clone, Debug)]
+pub struct ApplyOptions {
+    /// The language of the translations to apply.
+    pub language: String,
+    pub source_language: String,
+    /// Replace translated sentences in place, keeping the rest of the input as is.
+    pub splice: bool,
+    /// Wrap each sentence in an element with its id in the given attribute. Not available
+    /// with `splice` or `bilingual`.
+    pub sentence_ids: Option<(String, SentenceIdStyle)>,
+    /// Show the source next to its translation. Not available with `splice`.
+    pub bilingual: Option<BilingualLayout>,
+}
+
+impl ApplyOptions {
+    pub fn new(language: impl Into<String>) -> Self {
+        ApplyOptions {
+            language: language.into(),
+            source_language: "en-US".to_string(),
+            splice: false,
+            sentence_ids: None,
+            bilingual: None,
+        }
+    }
+
+    /// Returns the names of two options set together that cannot be combined, if any.
+    pub fn conflict(&self) -> Option<(&'static str, &'static str)> {
+        if self.splice && self.sentence_ids.is_some() {
+            Some(("splice", "sentence_ids"))
+        } else if self.splice && self.bilingual.is_some() {
+            Some(("splice", "bilingual"))
+        } else if self.sentence_ids.is_some() && self.bilingual.is_some() {
+            Some(("sentence_ids", "bilingual"))
+        } else {
+            None
+        }
+    }
+}
+
+/// Runs the application pipeline over `input_text` with the translations of the clause
+/// file `clauses_text`, and renders the translated document.
+pub fn apply_clause_file(
+    input_text: &str,
+    clauses_text: &str,
+    options: &ApplyOptions,
+) -> Result<String, ApplyError> {
+    if let Some((first, second)) = options.conflict() {
+        return Err(ApplyError::ConflictingOptions(first, second));
+    }
+    let clauses_reader = pulldown_cmark::Parser::new(clauses_text);
+    let clauses_ast = cmark_ast_from_tokens(clauses_reader, clauses_text)
+        .map_err(|e| ApplyError::ClauseFromTokens(Box::new(e)))?;
+    let clause_list = DocumentClauseList::from_clause_document(clauses_ast)?;
+
+    let reader = pulldown_cmark::Parser::new(input_text);
+    let mut ast = cmark_ast_from_tokens(reader, input_text)
+        .map_err(|e| ApplyError::FromTokens(Box::new(e)))?;
+    ast.perform_sentence_segment();
+    if options.splice {
+        return Ok(ast.splice_translations(input_text, &clause_list, &options.language)?);
+    }
+    let mut custom_tags = CustomTagRegistry::new();
+    if let Some((attribute, style)) = &options.sentence_ids {
+        let source_clause_list = ast.extract_clause_list(&options.source_language.as_str().into());
+        custom_tags.register(
+            crate::sentence_segment::SENTENCE_TAG_NAME,
+            CustomTagRendering::IdentifiedElement {
+                attribute: attribute.clone(),
+                ids: source_clause_list.sentence_ids(*style),
+            },
+        );
+    }
+    if let Some(layout) = options.bilingual {
+        ast = ast.into_bilingual(
+            &clause_list,
+            &options.source_language,
+            &options.language,
+            layout,
+        );
+    } else {
+        ast.apply_translations(&clause_list, &options.language);
+    }
+    let mut output_text = String::new();
+    let _ = pulldown_cmark_to_cmark::cmark(
+        ast.into_tokens_with_custom_tags(custom_tags),
+        &mut output_text,
+        None,
+    )?;
+    Ok(output_text)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::temp_dir;
+
+    fn translate_first_clause(clauses_text: &str, translation: &str) -> String {
+        let separator = clauses_text.find("---").unwrap();
+        format!(
+            "{}### zh-CN\n\n{}\n\n{}",
+            &clauses_text[..separator],
+            translation,
+            &clauses_text[separator..]
+        )
+    }
+
+    #[test]
+    fn markdown_files_are_found_outside_hidden_and_excluded_directories() {
+        let dir = temp_dir("find");
+        for path in &["b.md", "a/c.md", "a/d.txt", ".git/e.md", "out/f.md"] {
+            let path = dir.join(path);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, "Text.\n").unwrap();
+        }
+        let found = find_markdown_files(&dir, Some(&dir.join("out"))).unwrap();
+        assert_eq!(found, [Path::new("a/c.md"), Path::new("b.md")]);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn summary_chapters_are_listed_once_in_order() {
+        let dir = temp_dir("summary");
+        fs::write(
+            dir.join("SUMMARY.md"),
+            "# Summary\n\n- [Intro](./intro.md)\n- [Usage](usage.md#top)\n  \
+             - [Again](intro.md)\n- [Site](https://example.com/)\n",
+        )
+        .unwrap();
+        let chapters = find_summary_chapters(&dir.join("SUMMARY.md")).unwrap();
+        assert_eq!(chapters, [Path::new("intro.md"), Path::new("usage.md")]);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn update_keeps_translations_of_unchanged_clauses() {
+        let options = ExtractOptions::default();
+        let clauses_text =
+            extract_clause_file("Hello world. Second one.\n", "a.md", &options).unwrap();
+        let previous_text = translate_first_clause(&clauses_text, "你好世界。");
+        let updated_text = update_clause_file(
+            "Hello world. Second one, edited.\n",
+            "a.md",
+            Some(&previous_text),
+            &options,
+        )
+        .unwrap();
+        assert!(
+            updated_text.contains("### zh-CN\n\n你好世界。"),
+            "{}",
+            updated_text
+        );
+        assert_eq!(updated_text.matches("### zh-CN").count(), 1);
+    }
+
+    #[test]
+    fn sentence_ids_conflict_with_bilingual() {
+        let options = ApplyOptions {
+            sentence_ids: Some(("data-clause-id".to_string(), SentenceIdStyle::ClauseIndex)),
+            bilingual: Some(BilingualLayout::Interleaved),
+            ..ApplyOptions::new("zh-CN")
+        };
+        let clauses_text =
+            extract_clause_file("Hello world.\n", "a.md", &ExtractOptions::default()).unwrap();
+        assert!(matches!(
+            apply_clause_file("Hello world.\n", &clauses_text, &options),
+            Err(ApplyError::ConflictingOptions("sentence_ids", "bilingual"))
+        ));
+    }
+
+    #[test]
+    fn batch_update_keeps_translations() {
+        let dir = temp_dir("batch-update");
+        let options = ExtractOptions::default();
+        fs::write(dir.join("a.md"), "Hello world. Second one.\n").unwrap();
+        let items = BatchItem::plan(&dir, vec!["a.md".into()], &dir.join("clauses"));
+        items[0].update(&options).unwrap();
+        let clauses_text = fs::read_to_string(&items[0].output_path).unwrap();
+        let translated_text = translate_first_clause(&clauses_text, "你好世界。");
+        fs::write(&items[0].output_path, &translated_text).unwrap();
+
+        items[0].update(&options).unwrap();
+        assert_eq!(
+            fs::read_to_string(&items[0].output_path).unwrap(),
+            translated_text
+        );
+        items[0].extract(&options).unwrap();
+        assert_eq!(
+            fs::read_to_string(&items[0].output_path).unwrap(),
+            clauses_text
+        );
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,367 @@
+use crate::ast;
+use crate::clause;
+use crate::visit::{walk_inline_mut, Fold, VisitorMut};
+use core::mem;
+use std::vec;
+
+/// How `Document::into_bilingual` lays out the source and its translation.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum BilingualLayout {
+    /// Each block is followed by its translation, unless it has none.
+    Interleaved,
+    /// Paragraphs and headings become rows of a two-column table, source on the left.
+    /// Other blocks, which a table cell cannot hold, are interleaved between the tables.
+    Table,
+}
+
+fn leaf_is_translated(source: &ast::BlockNode<'_>, translated: &ast::BlockNode<'_>) -> bool {
+    use crate::textualize::textualize_inline_list;
+
+    match (source, translated) {
+        (
+            ast::BlockNode::Leaf { contents, .. },
+            ast::BlockNode::Leaf {
+                contents: translated_contents,
+                ..
+            },
+        ) => {
+            let mut source_text = String::new();
+            let mut translated_text = String::new();
+            textualize_inline_list(contents, &mut source_text);
+            textualize_inline_list(translated_contents, &mut translated_text);
+            source_text != translated_text
+        }
+        _ => false,
+    }
+}
+
+/// Replaces line breaks, which would end a table row, with their inline equivalents,
+/// and escapes pipes, which would end a cell.
+struct CellFitter;
+
+impl<'a> VisitorMut<'a> for CellFitter {
+    fn visit_inline_mut(&mut self, inline: &mut ast::InlineNode<'a>) {
+        match inline {
+            ast::InlineNode::Content { tag, .. } => match tag {
+                ast::ContentInlineTag::Text(text) | ast::ContentInlineTag::Code(text)
+                    if text.contains('|') =>
+                {
+                    *text = text.replace('|', "\\|").into();
+                }
+                ast::ContentInlineTag::SoftBreak => {
+                    *tag = ast::ContentInlineTag::Text(" ".into());
+                }
+                ast::ContentInlineTag::HardBreak => {
+                    *tag = ast::ContentInlineTag::RawHtml("<br>".into());
+                }
+                _ => {}
+            },
+            inline => walk_inline_mut(self, inline),
+        }
+    }
+}
+
+fn table_cell<'a>(
+    tag: &ast::LeafBlockTag<'a>,
+    mut contents: ast::InlineNodeList<'a>,
+) -> ast::BlockNode<'a> {
+    CellFitter.visit_inlines_mut(&mut contents);
+    if let ast::LeafBlockTag::Heading(_) = tag {
+        contents = vec![ast::InlineNode::Surrounding {
+            tag: ast::SurroundingInlineTag::Strong,
+            span: ast::SourceSpan::covering_inlines(&contents),
+            contents,
+        }];
+    }
+    ast::BlockNode::Leaf {
+        tag: ast::LeafBlockTag::TableCell,
+        span: ast::SourceSpan::covering_inlines(&contents),
+        contents,
+    }
+}
+
+fn text_cell<'a>(text: &str) -> ast::BlockNode<'a> {
+    ast::BlockNode::Leaf {
+        tag: ast::LeafBlockTag::TableCell,
+        contents: vec![ast::InlineNode::Content {
+            tag: ast::ContentInlineTag::Text(text.to_string().into()),
+            span: Default::default(),
+        }],
+        span: Default::default(),
+    }
+}
+
+struct TableBuilder<'a, 's> {
+    header: [&'s str; 2],
+    rows: ast::BlockNodeList<'a>,
+}
+
+impl<'a, 's> TableBuilder<'a, 's> {
+    fn push_row(&mut self, source: ast::BlockNode<'a>, translated: ast::BlockNode<'a>) {
+        let mut cells = Vec::new();
+        for block in [source, translated].iter_mut() {
+            if let ast::BlockNode::Leaf { tag, contents, .. } = block {
+                cells.push(table_cell(tag, mem::take(contents)));
+            }
+        }
+        self.rows.push(ast::BlockNode::Container {
+            tag: ast::ContainerBlockTag::TableRow,
+            children: cells,
+            span: Default::default(),
+        });
+    }
+
+    fn flush(&mut self, output: &mut ast::BlockNodeList<'a>) {
+        if self.rows.is_empty() {
+            return;
+        }
+        let head = ast::BlockNode::Container {
+            tag: ast::ContainerBlockTag::TableHead,
+            children: vec![text_cell(self.header[0]), text_cell(self.header[1])],
+            span: Default::default(),
+        };
+        let mut children = vec![head];
+        children.append(&mut self.rows);
+        output.push(ast::BlockNode::Container {
+            tag: ast::ContainerBlockTag::Table(vec![ast::Alignment::None; 2]),
+            children,
+            span: Default::default(),
+        });
+    }
+}
+
+/// Folds the source document into its bilingual form, consuming its translated copy in
+/// step with it.
+struct Interleaver<'a, 's> {
+    /// The translations of the blocks being folded.
+    translated: vec::IntoIter<ast::BlockNode<'a>>,
+    translated_outofbands: vec::IntoIter<ast::OutOfBandNode<'a>>,
+    /// The header of the tables holding the paragraphs and headings of the blocks being
+    /// folded, unless they are interleaved.
+    table_header: Option<[&'s str; 2]>,
+}
+
+impl<'a> Interleaver<'a, '_> {
+    /// Folds `children` in step with `translated_children`, interleaving them.
+    fn fold_children(
+        &mut self,
+        children: ast::BlockNodeList<'a>,
+        translated_children: ast::BlockNodeList<'a>,
+    ) -> ast::BlockNodeList<'a> {
+        let outer_translated = mem::replace(&mut self.translated, translated_children.into_iter());
+        let outer_header = self.table_header.take();
+        let children = self.fold_blocks(children);
+        self.translated = outer_translated;
+        self.table_header = outer_header;
+        children
+    }
+
+    fn interleave_block(
+        &mut self,
+        source: ast::BlockNode<'a>,
+        translated: ast::BlockNode<'a>,
+        output: &mut ast::BlockNodeList<'a>,
+    ) {
+        match (source, translated) {
+            (
+                ast::BlockNode::Container {
+                    tag,
+                    children,
+                    span,
+                },
+                ast::BlockNode::Container {
+                    children: translated_children,
+                    ..
+                },
+            ) if !matches!(tag, ast::ContainerBlockTag::Table(_)) => {
+                let children = self.fold_children(children, translated_children);
+                output.push(ast::BlockNode::Container {
+                    tag,
+                    children,
+                    span,
+                });
+            }
+            (source @ ast::BlockNode::Container { .. }, translated) => {
+                // tables can only be repeated as a whole
+                output.push(source);
+                output.push(translated);
+            }
+            (source @ ast::BlockNode::Leaf { .. }, translated) => {
+                let is_translated = leaf_is_translated(&source, &translated);
+                output.push(source);
+                if is_translated {
+                    output.push(translated);
+                }
+            }
+            (source @ ast::BlockNode::Markup { .. }, _) => {
+                output.push(source);
+            }
+        }
+    }
+}
+
+impl<'a> Fold<'a> for Interleaver<'a, '_> {
+    fn fold_blocks(&mut self, blocks: ast::BlockNodeList<'a>) -> ast::BlockNodeList<'a> {
+        let mut output = Vec::new();
+        let mut table = self.table_header.map(|header| TableBuilder {
+            header,
+            rows: Vec::new(),
+        });
+        for source in blocks {
+            let translated = match self.translated.next() {
+                Some(translated) => translated,
+                None => {
+                    output.push(source);
+                    continue;
+                }
+            };
+            match (&mut table, &source) {
+                (
+                    Some(table),
+                    ast::BlockNode::Leaf {
+                        tag: ast::LeafBlockTag::Paragraph | ast::LeafBlockTag::Heading(_),
+                        ..
+                    },
+                ) => table.push_row(source, translated),
+                (table, _) => {
+                    if let Some(table) = table {
+                        table.flush(&mut output);
+                    }
+                    self.interleave_block(source, translated, &mut output);
+                }
+            }
+        }
+        if let Some(table) = &mut table {
+            table.flush(&mut output);
+        }
+        output
+    }
+
+    fn fold_outofband(&mut self, source: ast::OutOfBandNode<'a>) -> ast::OutOfBandNode<'a> {
+        let translated = match self.translated_outofbands.next() {
+            Some(translated) => translated,
+            None => return source,
+        };
+        match (source, translated) {
+            (
+                ast::OutOfBandNode::OutOfBandContainer {
+                    tag,
+                    children,
+                    span,
+                },
+                ast::OutOfBandNode::OutOfBandContainer {
+                    children: translated_children,
+                    ..
+                },
+            ) => ast::OutOfBandNode::OutOfBandContainer {
+                tag,
+                children: self.fold_children(children, translated_children),
+                span,
+            },
+            (
+                ast::OutOfBandNode::OutOfBandLeaf {
+                    tag,
+                    mut contents,
+                    span,
+                },
+                ast::OutOfBandNode::OutOfBandLeaf {
+                    contents: translated_contents,
+                    ..
+                },
+            ) => {
+                contents.push(ast::InlineNode::Content {
+                    tag: ast::ContentInlineTag::HardBreak,
+                    span: Default::default(),
+                });
+                contents.extend(translated_contents);
+                ast::OutOfBandNode::OutOfBandLeaf {
+                    tag,
+                    contents,
+                    span,
+                }
+            }
+            (source, _) => source,
+        }
+    }
+}
+
+impl<'a> ast::Document<'a> {
+    /// Builds a document showing each part of this segmented document next to its
+    /// translation into `target_language`, to be rendered with `into_tokens`.
+    pub fn into_bilingual(
+        self,
+        clause_list: &clause::DocumentClauseList<'a>,
+        source_language: &str,
+        target_language: &str,
+        layout: BilingualLayout,
+    ) -> ast::Document<'a> {
+        let mut translated = self.clone();
+        translated.apply_translations(clause_list, target_language);
+
+        let table_header = match layout {
+            BilingualLayout::Interleaved => None,
+            BilingualLayout::Table => Some([source_language, target_language]),
+        };
+        self.fold(&mut Interleaver {
+            translated: translated.blocks.into_iter(),
+            translated_outofbands: translated.outofbands.into_iter(),
+            table_header,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{segmented, translate};
+
+    /// Translates clause `id` of `source` into `translation` and renders it bilingually.
+    fn bilingual(source: &str, id: usize, translation: &str, layout: BilingualLayout) -> String {
+        let doc = segmented(source);
+        let mut clause_list = doc.extract_clause_list(&"en-US".into()).into_owned();
+        translate(&mut clause_list, id, "zh-CN", translation);
+        let doc = doc.into_bilingual(&clause_list, "en-US", "zh-CN", layout);
+        let mut output = String::new();
+        pulldown_cmark_to_cmark::cmark(doc.into_tokens(), &mut output, None).unwrap();
+        output
+    }
+
+    #[test]
+    fn interleaving_follows_translated_paragraphs_only() {
+        let output = bilingual(
+            "One.\n\n> Two.\n\nThree.\n",
+            2,
+            "二。",
+            BilingualLayout::Interleaved,
+        );
+        assert_eq!(output, "One.\n\n > \n > Two.\n > \n > 二。\n\nThree.");
+    }
+
+    #[test]
+    fn tables_put_the_translation_next_to_its_source() {
+        let output = bilingual("# One.\n\nTwo.\n", 2, "二。", BilingualLayout::Table);
+        assert_eq!(
+            output,
+            "|en-US|zh-CN|\n|-----|-----|\n|**One.**|**One.**|\n|Two.|二。|"
+        );
+    }
+
+    #[test]
+    fn table_cells_escape_pipes() {
+        let output = bilingual("A | B. Next.\n", 2, "下一个。", BilingualLayout::Table);
+        assert!(
+            output.ends_with("|A \\| B. Next.|A \\| B. 下一个。|"),
+            "{}",
+            output
+        );
+        let cells = pulldown_cmark::Parser::new_ext(&output, pulldown_cmark::Options::all())
+            .filter(|event| {
+                matches!(
+                    event,
+                    pulldown_cmark::Event::Start(pulldown_cmark::Tag::TableCell)
+                )
+            })
+            .count();
+        assert_eq!(cells, 4);
+    }
+}
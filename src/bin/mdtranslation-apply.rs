@@ -0,0 +1,161 @@
+use mdtranslation::batch::{self, ApplyOptions, BatchItem};
+use mdtranslation::bilingual::BilingualLayout;
+use mdtranslation::clause::SentenceIdStyle;
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use structopt::StructOpt;
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+enum Error {
+    #[error("io error: {0}")]
+    IO(#[from] io::Error),
+    #[error("apply error: {0}")]
+    Apply(#[from] batch::ApplyError),
+    #[error("{0}")]
+    Batch(#[from] batch::BatchError),
+    #[error("an output directory is required when applying to a whole book")]
+    MissingOutputDir,
+    #[error("{0} files failed")]
+    Failed(usize),
+}
+
+#[derive(Debug)]
+enum SentenceSpans {
+    ClauseId,
+    StableId,
+}
+
+impl FromStr for SentenceSpans {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "clause-id" => Ok(SentenceSpans::ClauseId),
+            "stable-id" => Ok(SentenceSpans::StableId),
+            _ => Err(format!("unknown sentence span style: {}", s)),
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Bilingual(BilingualLayout);
+
+impl FromStr for Bilingual {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "interleaved" => Ok(Bilingual(BilingualLayout::Interleaved)),
+            "table" => Ok(Bilingual(BilingualLayout::Table)),
+            _ => Err(format!("unknown bilingual layout: {}", s)),
+        }
+    }
+}
+
+#[derive(Debug, StructOpt)]
+struct Opt {
+    /// Source language input file, or a directory to apply to every Markdown file under
+    #[structopt(parse(from_os_str))]
+    input: PathBuf,
+
+    /// Clause file containing the translations; directory of clause files for a whole book
+    #[structopt(parse(from_os_str))]
+    clauses: PathBuf,
+
+    /// Target language to apply
+    #[structopt(short, long)]
+    language: String,
+
+    /// Language of the input file
+    #[structopt(long, default_value = "en-US")]
+    source_language: String,
+
+    /// Replace translated sentences in place, keeping the rest of the input byte-for-byte
+    #[structopt(long)]
+    splice: bool,
+
+    /// Wrap each sentence in a span carrying its id, either `clause-id` (data-clause-id,
+    /// the clause index) or `stable-id` (data-mdt-id, a digest of the source sentence)
+    #[structopt(long, conflicts_with = "splice")]
+    sentence_spans: Option<SentenceSpans>,
+
+    /// Show the source next to its translation, either `interleaved` or as a `table`
+    #[structopt(long, conflicts_with_all = &["splice", "sentence-spans"])]
+    bilingual: Option<Bilingual>,
+
+    /// Output file, stdout if not present; output directory when applying to a whole book
+    #[structopt(parse(from_os_str))]
+    output: Option<PathBuf>,
+
+    /// Number of files translated at once in a whole book, one per CPU by default
+    #[structopt(short, long)]
+    jobs: Option<usize>,
+}
+
+fn apply_book(opt: &Opt, output_dir: &Path, options: &ApplyOptions) -> Result<(), Error> {
+    // files without a clause file have nothing to translate
+    let relative_paths = batch::find_markdown_files(&opt.input, Some(output_dir))?
+        .into_iter()
+        .filter(|relative_path| opt.clauses.join(relative_path).is_file())
+        .collect();
+    let items = BatchItem::plan(&opt.input, relative_paths, output_dir);
+    let jobs = opt.jobs.unwrap_or_else(batch::default_jobs);
+    let results = batch::run_parallel(&items, jobs, |item| item.apply(&opt.clauses, options));
+    let mut failed = 0;
+    for (item, result) in items.iter().zip(results) {
+        match result {
+            Ok(()) => eprintln!("{}", item.output_path.display()),
+            Err(e) => {
+                eprintln!("{}", e);
+                failed += 1;
+            }
+        }
+    }
+    if failed != 0 {
+        return Err(Error::Failed(failed));
+    }
+    Ok(())
+}
+
+fn main() -> Result<(), Error> {
+    let opt = Opt::from_args();
+    let options = ApplyOptions {
+        language: opt.language.clone(),
+        source_language: opt.source_language.clone(),
+        splice: opt.splice,
+        sentence_ids: opt
+            .sentence_spans
+            .as_ref()
+            .map(|sentence_spans| match sentence_spans {
+                SentenceSpans::ClauseId => {
+                    ("data-clause-id".to_string(), SentenceIdStyle::ClauseIndex)
+                }
+                SentenceSpans::StableId => {
+                    ("data-mdt-id".to_string(), SentenceIdStyle::ContentDigest)
+                }
+            }),
+        bilingual: opt.bilingual.as_ref().map(|Bilingual(layout)| *layout),
+    };
+    if opt.input.is_dir() {
+        let output_dir = opt.output.as_ref().ok_or(Error::MissingOutputDir)?;
+        return apply_book(&opt, output_dir, &options);
+    }
+
+    let input_text = fs::read_to_string(&opt.input)?;
+    let clauses_text = fs::read_to_string(&opt.clauses)?;
+    let output_text = batch::apply_clause_file(&input_text, &clauses_text, &options)?;
+    // the output may be the input itself, so it is only created once the input is read
+    let stdout;
+    let mut output_file: Box<dyn io::Write + '_> = if let Some(output_path) = opt.output.as_ref() {
+        let file = fs::File::create(output_path)?;
+        Box::new(file) as _
+    } else {
+        stdout = Some(io::stdout());
+        let stdout_lock = stdout.as_ref().unwrap().lock();
+        Box::new(stdout_lock) as _
+    };
+    output_file.write_all(output_text.as_bytes())?;
+
+    Ok(())
+}
@@ -1,7 +1,11 @@
-use std::fmt;
+use mdtranslation::batch::{self, ApplyOptions, BatchError, BatchItem, ExtractOptions};
+use mdtranslation::glossary::{Glossary, GlossaryFormat};
+use std::collections::{HashMap, HashSet};
 use std::fs;
 use std::io::{self, Read, Write};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::{Duration, SystemTime};
 use structopt::StructOpt;
 use thiserror::Error;
 
@@ -9,26 +13,295 @@ use thiserror::Error;
 enum Error {
     #[error("io error: {0}")]
     IO(#[from] io::Error),
-    #[error("format error: {0}")]
-    Fmt(#[from] fmt::Error),
-    #[error("from tokens error: {0}")]
-    FromTokens(#[from] mdtranslation::from_tokens::FromTokensError),
+    #[error("extract error: {0}")]
+    Extract(#[from] batch::ExtractError),
+    #[error("{0}")]
+    Batch(#[from] batch::BatchError),
+    #[error("glossary error: {0}")]
+    Glossary(#[from] mdtranslation::glossary::GlossaryError),
+    #[error("an output directory is required when extracting a whole book")]
+    MissingOutputDir,
+    #[error("{0} files failed")]
+    Failed(usize),
+    #[error("watch mode only works on a whole book")]
+    WatchSingleFile,
 }
 
 #[derive(Debug, StructOpt)]
 struct Opt {
-    /// Input file
+    /// Input file, or a directory to extract every Markdown file under
     #[structopt(parse(from_os_str))]
     input: PathBuf,
 
-    /// Output file, stdout if not present
+    /// Output file, stdout if not present; output directory when extracting a whole book.
+    /// The translations of clause files being replaced are kept
     #[structopt(parse(from_os_str))]
     output: Option<PathBuf>,
+
+    /// Treat the input as an mdBook `SUMMARY.md` and extract every chapter it links to
+    #[structopt(long)]
+    summary: bool,
+
+    /// Language of the input files
+    #[structopt(long, default_value = "en-US")]
+    source_language: String,
+
+    /// Extract every file in a whole book, even those whose clause file is up to date
+    #[structopt(long)]
+    force: bool,
+
+    /// Glossary (TSV, or CSV by extension) whose terms are listed with each clause
+    #[structopt(long, parse(from_os_str))]
+    glossary: Option<PathBuf>,
+
+    /// Prefix of the HTML comments holding notes for translators
+    #[structopt(long, default_value = mdtranslation::notes::DEFAULT_NOTE_PREFIX)]
+    note_prefix: String,
+
+    /// Number of files extracted at once in a whole book, one per CPU by default
+    #[structopt(short, long)]
+    jobs: Option<usize>,
+
+    /// Keep running, re-extracting the files of a whole book as they change while keeping
+    /// the translations of their clause files
+    #[structopt(long)]
+    watch: bool,
+
+    /// Directory to keep translated previews of the book in, in watch mode
+    #[structopt(long, parse(from_os_str), requires_all = &["watch", "preview-language"])]
+    preview: Option<PathBuf>,
+
+    /// Language of the translated previews
+    #[structopt(long, requires = "preview")]
+    preview_language: Option<String>,
+
+    /// Milliseconds between two checks for changes in watch mode
+    #[structopt(long, default_value = "1000")]
+    interval: u64,
+}
+
+fn load_glossary(opt: &Opt) -> Result<Option<Glossary>, Error> {
+    let path = match &opt.glossary {
+        Some(path) => path,
+        None => return Ok(None),
+    };
+    let format = GlossaryFormat::from_file_name(&path.to_string_lossy());
+    Ok(Some(Glossary::parse(&fs::read_to_string(path)?, format)?))
+}
+
+fn book_files<'o>(opt: &'o Opt, output_dir: &Path) -> Result<(&'o Path, Vec<PathBuf>), Error> {
+    if opt.summary {
+        let input_dir = opt.input.parent().unwrap_or_else(|| Path::new(""));
+        Ok((input_dir, batch::find_summary_chapters(&opt.input)?))
+    } else {
+        let relative_paths = batch::find_markdown_files(&opt.input, Some(output_dir))?;
+        Ok((opt.input.as_path(), relative_paths))
+    }
+}
+
+/// The relative paths of the items whose processing failed.
+fn failures<T>(items: &[BatchItem], results: &[Result<T, BatchError>]) -> HashSet<PathBuf> {
+    items
+        .iter()
+        .zip(results)
+        .filter(|(_, result)| result.is_err())
+        .map(|(item, _)| item.relative_path.clone())
+        .collect()
+}
+
+/// Prints the output of each item processed and each error, returning the number of
+/// errors.
+fn report(items: &[BatchItem], results: Vec<Result<bool, BatchError>>) -> usize {
+    let mut failed = 0;
+    for (item, result) in items.iter().zip(results) {
+        match result {
+            Ok(true) => eprintln!("{}", item.output_path.display()),
+            Ok(false) => {}
+            Err(e) => {
+                eprintln!("{}", e);
+                failed += 1;
+            }
+        }
+    }
+    failed
+}
+
+fn extract_book(opt: &Opt, output_dir: &Path, options: &ExtractOptions<'_>) -> Result<(), Error> {
+    let (input_dir, relative_paths) = book_files(opt, output_dir)?;
+    let items = BatchItem::plan(input_dir, relative_paths, output_dir);
+    let jobs = opt.jobs.unwrap_or_else(batch::default_jobs);
+    let results = batch::run_parallel(&items, jobs, |item| {
+        if !opt.force && item.is_up_to_date() {
+            return Ok(false);
+        }
+        item.update(options).map(|()| true)
+    });
+    let failed = report(&items, results);
+    if failed != 0 {
+        return Err(Error::Failed(failed));
+    }
+    Ok(())
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+/// Where and in which language the translated previews go.
+struct Preview<'o> {
+    dir: &'o Path,
+    /// `dir` canonicalized, to leave the previews out when it is inside the book.
+    canonical_dir: PathBuf,
+    options: ApplyOptions,
+}
+
+/// The modification times of each file and of its clause file when last checked.
+type Seen = HashMap<PathBuf, (Option<SystemTime>, Option<SystemTime>)>;
+
+/// Updates the clause files of the files changed since the last round, then the previews
+/// of those whose source or clause file changed.
+fn watch_round(
+    opt: &Opt,
+    output_dir: &Path,
+    options: &ExtractOptions<'_>,
+    preview: Option<&Preview<'_>>,
+    seen: &mut Seen,
+) -> Result<(), Error> {
+    let (input_dir, mut relative_paths) = book_files(opt, output_dir)?;
+    if let Some(preview) = preview {
+        relative_paths.retain(|path| {
+            input_dir
+                .join(path)
+                .canonicalize()
+                .map_or(true, |path| !path.starts_with(&preview.canonical_dir))
+        });
+    }
+    let current: HashSet<&PathBuf> = relative_paths.iter().collect();
+    seen.retain(|path, _| current.contains(path));
+    let items = BatchItem::plan(input_dir, relative_paths, output_dir);
+    let jobs = opt.jobs.unwrap_or_else(batch::default_jobs);
+
+    let input_times: Vec<_> = items
+        .iter()
+        .map(|item| modified(&item.input_path))
+        .collect();
+    let changed: Vec<BatchItem> = items
+        .iter()
+        .zip(&input_times)
+        .filter(|(item, input_time)| match seen.get(&item.relative_path) {
+            Some((seen_time, _)) => seen_time != *input_time,
+            None => opt.force || !item.is_up_to_date(),
+        })
+        .map(|(item, _)| item.clone())
+        .collect();
+    let results = batch::run_parallel(&changed, jobs, |item| item.update(options).map(|()| true));
+    let mut failed = failures(&changed, &results);
+    report(&changed, results);
+
+    let clause_times: Vec<_> = items
+        .iter()
+        .map(|item| modified(&item.output_path))
+        .collect();
+    if let Some(preview) = preview {
+        let previews: Vec<BatchItem> = items
+            .iter()
+            .zip(input_times.iter().zip(&clause_times))
+            .filter(|(item, (input_time, clause_time))| {
+                if clause_time.is_none() {
+                    return false;
+                }
+                match seen.get(&item.relative_path) {
+                    Some((seen_input_time, seen_clause_time)) => {
+                        seen_input_time != *input_time || seen_clause_time != *clause_time
+                    }
+                    None => {
+                        let preview_time = modified(&preview.dir.join(&item.relative_path));
+                        preview_time < **input_time || preview_time < **clause_time
+                    }
+                }
+            })
+            .map(|(item, _)| BatchItem {
+                relative_path: item.relative_path.clone(),
+                input_path: item.input_path.clone(),
+                output_path: preview.dir.join(&item.relative_path),
+            })
+            .collect();
+        let results = batch::run_parallel(&previews, jobs, |item| {
+            item.apply(output_dir, &preview.options).map(|()| true)
+        });
+        failed.extend(failures(&previews, &results));
+        report(&previews, results);
+    }
+
+    // failed files stay unseen, to be retried in the next round
+    for ((item, input_time), clause_time) in items.iter().zip(input_times).zip(clause_times) {
+        if !failed.contains(&item.relative_path) {
+            seen.insert(item.relative_path.clone(), (input_time, clause_time));
+        }
+    }
+    Ok(())
+}
+
+fn watch_book(opt: &Opt, output_dir: &Path, options: &ExtractOptions<'_>) -> Result<(), Error> {
+    let preview = match (&opt.preview, &opt.preview_language) {
+        (Some(dir), Some(language)) => {
+            fs::create_dir_all(dir)?;
+            Some(Preview {
+                dir,
+                canonical_dir: dir.canonicalize()?,
+                options: ApplyOptions {
+                    source_language: opt.source_language.clone(),
+                    ..ApplyOptions::new(language.as_str())
+                },
+            })
+        }
+        _ => None,
+    };
+    let mut seen = Seen::new();
+    loop {
+        // a file may be missing for a moment while it is saved, so errors do not stop
+        // the watch
+        if let Err(e) = watch_round(opt, output_dir, options, preview.as_ref(), &mut seen) {
+            eprintln!("{}", e);
+        }
+        thread::sleep(Duration::from_millis(opt.interval));
+    }
 }
 
 fn main() -> Result<(), Error> {
     let opt = Opt::from_args();
-    let mut input = fs::File::open(opt.input)?;
+    let glossary = load_glossary(&opt)?;
+    let options = ExtractOptions {
+        source_language: opt.source_language.clone(),
+        glossary: glossary.as_ref(),
+        note_prefix: opt.note_prefix.clone(),
+    };
+    if opt.summary || opt.input.is_dir() {
+        let output_dir = opt.output.as_ref().ok_or(Error::MissingOutputDir)?;
+        if opt.watch {
+            return watch_book(&opt, output_dir, &options);
+        }
+        return extract_book(&opt, output_dir, &options);
+    }
+    if opt.watch {
+        return Err(Error::WatchSingleFile);
+    }
+
+    let mut input = fs::File::open(&opt.input)?;
+    let mut input_text = String::new();
+    let _ = input.read_to_string(&mut input_text)?;
+    let previous_text = match opt.output.as_ref().map(fs::read_to_string) {
+        Some(Ok(text)) => Some(text),
+        Some(Err(e)) if e.kind() != io::ErrorKind::NotFound => return Err(e.into()),
+        _ => None,
+    };
+    let output_text = batch::update_clause_file(
+        &input_text,
+        &opt.input.display().to_string(),
+        previous_text.as_deref(),
+        &options,
+    )?;
+
     let stdout;
     let mut output_file: Box<dyn io::Write + '_> = if let Some(output_path) = opt.output.as_ref() {
         let file = fs::File::create(output_path)?;
@@ -38,14 +311,6 @@ fn main() -> Result<(), Error> {
         let stdout_lock = stdout.as_ref().unwrap().lock();
         Box::new(stdout_lock) as _
     };
-    let mut input_text = String::new();
-    let _ = input.read_to_string(&mut input_text)?;
-    let reader = pulldown_cmark::Parser::new(&input_text);
-    let mut ast = mdtranslation::from_tokens::cmark_ast_from_tokens(reader)?;
-    ast.perform_sentence_segment();
-    let clause_list = ast.extract_clause_list(&pulldown_cmark::CowStr::Borrowed("en-US"));
-    let mut output_text = String::new();
-    let _ = pulldown_cmark_to_cmark::cmark(clause_list.into_tokens(), &mut output_text, None)?;
     output_file.write_all(output_text.as_bytes())?;
 
     Ok(())
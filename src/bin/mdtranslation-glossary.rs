@@ -0,0 +1,61 @@
+use mdtranslation::glossary::{Glossary, GlossaryFormat};
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+use std::process;
+use structopt::StructOpt;
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+enum Error {
+    #[error("io error: {0}")]
+    IO(#[from] io::Error),
+    #[error("glossary error: {0}")]
+    Glossary(#[from] mdtranslation::glossary::GlossaryError),
+    #[error("{0}: from tokens error: {1}")]
+    FromTokens(PathBuf, Box<mdtranslation::from_tokens::FromTokensError>),
+    #[error("{0}: clause file error: {1}")]
+    ClauseFile(PathBuf, mdtranslation::clause::ClauseFileError),
+}
+
+#[derive(Debug, StructOpt)]
+struct Opt {
+    /// Glossary of the language pair, TSV or CSV by extension
+    #[structopt(parse(from_os_str))]
+    glossary: PathBuf,
+
+    /// Clause files to check
+    #[structopt(parse(from_os_str), required = true)]
+    clauses: Vec<PathBuf>,
+
+    /// Target language to check
+    #[structopt(short, long)]
+    language: String,
+}
+
+fn main() -> Result<(), Error> {
+    let opt = Opt::from_args();
+    let format = GlossaryFormat::from_file_name(&opt.glossary.to_string_lossy());
+    let glossary = Glossary::parse(&fs::read_to_string(&opt.glossary)?, format)?;
+
+    let mut violation_count = 0;
+    for path in &opt.clauses {
+        let clauses_text = fs::read_to_string(path)?;
+        let clauses_reader = pulldown_cmark::Parser::new(&clauses_text);
+        let clauses_ast =
+            mdtranslation::from_tokens::cmark_ast_from_tokens(clauses_reader, &clauses_text)
+                .map_err(|e| Error::FromTokens(path.clone(), Box::new(e)))?;
+        let clause_list =
+            mdtranslation::clause::DocumentClauseList::from_clause_document(clauses_ast)
+                .map_err(|e| Error::ClauseFile(path.clone(), e))?;
+        for violation in clause_list.check_glossary(&glossary, &opt.language) {
+            println!("{}: {}", path.display(), violation);
+            violation_count += 1;
+        }
+    }
+    if violation_count > 0 {
+        process::exit(1);
+    }
+
+    Ok(())
+}
@@ -0,0 +1,46 @@
+//! An mdBook preprocessor applying the translations from clause files.
+//!
+//! See `mdtranslation::mdbook` for its configuration. The `MDTRANSLATION_LANGUAGE`
+//! environment variable overrides the configured language, so one book can be built
+//! for several languages. Without a language, chapters are passed through unchanged.
+use std::io::{self, Read, Write};
+use structopt::StructOpt;
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+enum Error {
+    #[error("io error: {0}")]
+    IO(#[from] io::Error),
+    #[error("{0}")]
+    Mdbook(#[from] mdtranslation::mdbook::MdbookError),
+}
+
+#[derive(Debug, StructOpt)]
+struct Opt {
+    #[structopt(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Debug, StructOpt)]
+enum Command {
+    /// Check whether the given renderer is supported, which every renderer is
+    Supports {
+        #[allow(dead_code)]
+        renderer: String,
+    },
+}
+
+fn main() -> Result<(), Error> {
+    let opt = Opt::from_args();
+    if let Some(Command::Supports { .. }) = opt.command {
+        return Ok(());
+    }
+
+    let mut input_text = String::new();
+    let _ = io::stdin().read_to_string(&mut input_text)?;
+    let language = std::env::var("MDTRANSLATION_LANGUAGE").ok();
+    let output_text = mdtranslation::mdbook::preprocess(&input_text, language)?;
+    io::stdout().write_all(output_text.as_bytes())?;
+
+    Ok(())
+}
@@ -0,0 +1,61 @@
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+use std::process;
+use structopt::StructOpt;
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+enum Error {
+    #[error("io error: {0}")]
+    IO(#[from] io::Error),
+    #[error("{0}: from tokens error: {1}")]
+    FromTokens(PathBuf, Box<mdtranslation::from_tokens::FromTokensError>),
+    #[error("{0}: clause file error: {1}")]
+    ClauseFile(PathBuf, mdtranslation::clause::ClauseFileError),
+}
+
+/// Check the translations in clause files for numbers, urls and code that differ from
+/// the source, and for mistakes in punctuation and spacing
+#[derive(Debug, StructOpt)]
+struct Opt {
+    /// Clause files to check
+    #[structopt(parse(from_os_str), required = true)]
+    clauses: Vec<PathBuf>,
+
+    /// Target language to check
+    #[structopt(short, long)]
+    language: String,
+
+    /// Exit with status 1 when any issue is found
+    #[structopt(long)]
+    fail_on_issues: bool,
+}
+
+fn main() -> Result<(), Error> {
+    let opt = Opt::from_args();
+
+    let mut issue_count = 0;
+    for path in &opt.clauses {
+        let clauses_text = fs::read_to_string(path)?;
+        let clauses_reader = pulldown_cmark::Parser::new(&clauses_text);
+        let clauses_ast =
+            mdtranslation::from_tokens::cmark_ast_from_tokens(clauses_reader, &clauses_text)
+                .map_err(|e| Error::FromTokens(path.clone(), Box::new(e)))?;
+        let clause_list =
+            mdtranslation::clause::DocumentClauseList::from_clause_document(clauses_ast)
+                .map_err(|e| Error::ClauseFile(path.clone(), e))?;
+        for diagnostic in clause_list.check_translations(&opt.language) {
+            println!("{}: {}", path.display(), diagnostic);
+            issue_count += 1;
+        }
+    }
+    if issue_count > 0 {
+        eprintln!("{} issues found", issue_count);
+        if opt.fail_on_issues {
+            process::exit(1);
+        }
+    }
+
+    Ok(())
+}
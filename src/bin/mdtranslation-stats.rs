@@ -0,0 +1,183 @@
+use mdtranslation::stats::ClauseListStats;
+use serde_json::{json, Value};
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use structopt::StructOpt;
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+enum Error {
+    #[error("io error: {0}")]
+    IO(#[from] io::Error),
+    #[error("json error: {0}")]
+    Json(#[from] serde_json::Error),
+    #[error("{0}")]
+    Batch(#[from] mdtranslation::batch::BatchError),
+    #[error("{0}: from tokens error: {1}")]
+    FromTokens(PathBuf, Box<mdtranslation::from_tokens::FromTokensError>),
+    #[error("{0}: clause file error: {1}")]
+    ClauseFile(PathBuf, mdtranslation::clause::ClauseFileError),
+}
+
+#[derive(Debug, StructOpt)]
+struct Opt {
+    /// Clause files, or directories to report every clause file under
+    #[structopt(parse(from_os_str), required = true)]
+    inputs: Vec<PathBuf>,
+
+    /// Languages to report, every translated language if not present
+    #[structopt(short, long, number_of_values = 1)]
+    language: Vec<String>,
+
+    /// Language the clause files were extracted from
+    #[structopt(long, default_value = "en-US")]
+    source_language: String,
+
+    /// Write the report as JSON instead of a text table
+    #[structopt(long)]
+    json: bool,
+}
+
+fn file_stats(path: &Path, opt: &Opt) -> Result<ClauseListStats, Error> {
+    let clauses_text = fs::read_to_string(path)?;
+    let clauses_reader = pulldown_cmark::Parser::new(&clauses_text);
+    let clauses_ast =
+        mdtranslation::from_tokens::cmark_ast_from_tokens(clauses_reader, &clauses_text)
+            .map_err(|e| Error::FromTokens(path.to_owned(), Box::new(e)))?;
+    let clause_list = mdtranslation::clause::DocumentClauseList::from_clause_document(clauses_ast)
+        .map_err(|e| Error::ClauseFile(path.to_owned(), e))?;
+    let languages: Vec<&str> = opt.language.iter().map(String::as_str).collect();
+    Ok(clause_list.stats(&opt.source_language, &languages))
+}
+
+fn stats_json(name: &str, stats: &ClauseListStats) -> Value {
+    let languages: Vec<Value> = stats
+        .languages
+        .iter()
+        .map(|language| {
+            json!({
+                "language": language.language,
+                "translated": language.translated,
+                "fuzzy": language.fuzzy,
+                "untranslated": language.untranslated,
+                "remaining_words": language.remaining_words,
+                "percent_complete": language.percent_complete(),
+            })
+        })
+        .collect();
+    json!({
+        "file": name,
+        "clauses": stats.clauses,
+        "source_words": stats.source_words,
+        "source_chars": stats.source_chars,
+        "languages": languages,
+    })
+}
+
+fn stats_rows(name: &str, stats: &ClauseListStats, rows: &mut Vec<[String; 10]>) {
+    let counts = [
+        name.to_string(),
+        stats.clauses.to_string(),
+        stats.source_words.to_string(),
+        stats.source_chars.to_string(),
+    ];
+    if stats.languages.is_empty() {
+        let mut row: [String; 10] = Default::default();
+        row[..4].clone_from_slice(&counts);
+        row[4] = "-".to_string();
+        rows.push(row);
+    }
+    for language in &stats.languages {
+        let mut row: [String; 10] = Default::default();
+        row[..4].clone_from_slice(&counts);
+        row[4] = language.language.clone();
+        row[5] = language.translated.to_string();
+        row[6] = language.fuzzy.to_string();
+        row[7] = language.untranslated.to_string();
+        row[8] = language.remaining_words.to_string();
+        row[9] = format!("{:.1}%", language.percent_complete());
+        rows.push(row);
+    }
+}
+
+fn write_table(rows: &[[String; 10]], output: &mut dyn Write) -> io::Result<()> {
+    let mut widths = [0; 10];
+    for row in rows {
+        for (width, cell) in widths.iter_mut().zip(row.iter()) {
+            *width = (*width).max(cell.chars().count());
+        }
+    }
+    for row in rows {
+        let mut line = String::new();
+        for (idx, (width, cell)) in widths.iter().zip(row.iter()).enumerate() {
+            let padding = " ".repeat(width - cell.chars().count());
+            // names and languages are left-aligned, numbers right-aligned
+            if idx == 0 || idx == 4 {
+                line += &format!("{}{}  ", cell, padding);
+            } else {
+                line += &format!("{}{}  ", padding, cell);
+            }
+        }
+        writeln!(output, "{}", line.trim_end())?;
+    }
+    Ok(())
+}
+
+fn main() -> Result<(), Error> {
+    let opt = Opt::from_args();
+    let mut files = Vec::new();
+    for input in &opt.inputs {
+        if input.is_dir() {
+            for relative_path in mdtranslation::batch::find_markdown_files(input, None)? {
+                files.push(input.join(relative_path));
+            }
+        } else {
+            files.push(input.clone());
+        }
+    }
+
+    let mut total = ClauseListStats::default();
+    let mut file_reports = Vec::new();
+    for path in &files {
+        let stats = file_stats(path, &opt)?;
+        total.add(&stats);
+        file_reports.push((path.display().to_string(), stats));
+    }
+
+    let stdout = io::stdout();
+    let mut output = stdout.lock();
+    if opt.json {
+        let report = json!({
+            "files": file_reports
+                .iter()
+                .map(|(name, stats)| stats_json(name, stats))
+                .collect::<Vec<_>>(),
+            "total": stats_json("total", &total),
+        });
+        writeln!(output, "{}", serde_json::to_string_pretty(&report)?)?;
+    } else {
+        let header = [
+            "file",
+            "clauses",
+            "words",
+            "chars",
+            "language",
+            "translated",
+            "fuzzy",
+            "untranslated",
+            "remaining words",
+            "complete",
+        ];
+        let mut rows = vec![header.map(String::from)];
+        for (name, stats) in &file_reports {
+            stats_rows(name, stats, &mut rows);
+        }
+        if file_reports.len() > 1 {
+            stats_rows("total", &total, &mut rows);
+        }
+        write_table(&rows, &mut output)?;
+    }
+
+    Ok(())
+}
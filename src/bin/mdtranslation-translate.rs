@@ -0,0 +1,101 @@
+use mdtranslation::pseudo::PseudoTranslator;
+use mdtranslation::translator::{CommandTranslator, MockTranslator, Translator};
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+use structopt::StructOpt;
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+enum Error {
+    #[error("io error: {0}")]
+    IO(#[from] io::Error),
+    #[error("format error: {0}")]
+    Fmt(#[from] fmt::Error),
+    #[error("{0}: from tokens error: {1}")]
+    FromTokens(PathBuf, Box<mdtranslation::from_tokens::FromTokensError>),
+    #[error("{0}: clause file error: {1}")]
+    ClauseFile(PathBuf, mdtranslation::clause::ClauseFileError),
+    #[error("{0}: {1}")]
+    Translator(PathBuf, mdtranslation::translator::TranslatorError),
+    #[error("one of --command, --mock or --pseudo is required")]
+    MissingBackend,
+}
+
+/// Pre-fill the untranslated clauses of clause files with machine translations
+#[derive(Debug, StructOpt)]
+struct Opt {
+    /// Clause files to fill in, rewritten in place
+    #[structopt(parse(from_os_str), required = true)]
+    clauses: Vec<PathBuf>,
+
+    /// Target language to translate into
+    #[structopt(short, long)]
+    language: String,
+
+    /// Language the clause files were extracted from
+    #[structopt(long, default_value = "en-US")]
+    source_language: String,
+
+    /// Executable translating the JSON requests written to its standard input
+    #[structopt(long, parse(from_os_str), conflicts_with_all = &["mock", "pseudo"])]
+    command: Option<PathBuf>,
+
+    /// Argument to pass to the executable, may be repeated
+    #[structopt(long = "arg", number_of_values = 1, allow_hyphen_values = true)]
+    args: Vec<String>,
+
+    /// Use a mock backend which only prefixes the target language
+    #[structopt(long, conflicts_with = "pseudo")]
+    mock: bool,
+
+    /// Fill in pseudo-localized text, usually for the `qps-ploc` language
+    #[structopt(long)]
+    pseudo: bool,
+
+    /// Number of clauses sent to the backend at once
+    #[structopt(long, default_value = "32")]
+    batch_size: usize,
+}
+
+fn main() -> Result<(), Error> {
+    let opt = Opt::from_args();
+    let mut translator: Box<dyn Translator> = match &opt.command {
+        Some(program) => Box::new(CommandTranslator {
+            program: program.clone(),
+            args: opt.args.clone(),
+        }),
+        None if opt.mock => Box::new(MockTranslator),
+        None if opt.pseudo => Box::new(PseudoTranslator),
+        None => return Err(Error::MissingBackend),
+    };
+
+    for path in &opt.clauses {
+        let clauses_text = fs::read_to_string(path)?;
+        let clauses_reader = pulldown_cmark::Parser::new(&clauses_text);
+        let clauses_ast =
+            mdtranslation::from_tokens::cmark_ast_from_tokens(clauses_reader, &clauses_text)
+                .map_err(|e| Error::FromTokens(path.clone(), Box::new(e)))?;
+        let mut clause_list =
+            mdtranslation::clause::DocumentClauseList::from_clause_document(clauses_ast)
+                .map_err(|e| Error::ClauseFile(path.clone(), e))?;
+        let count = clause_list
+            .prefill_translations(
+                translator.as_mut(),
+                &opt.source_language,
+                &opt.language,
+                opt.batch_size,
+            )
+            .map_err(|e| Error::Translator(path.clone(), e))?;
+        if count == 0 {
+            continue;
+        }
+        let mut output_text = String::new();
+        let _ = pulldown_cmark_to_cmark::cmark(clause_list.into_tokens(), &mut output_text, None)?;
+        fs::write(path, output_text)?;
+        eprintln!("{}: {} clauses translated", path.display(), count);
+    }
+
+    Ok(())
+}
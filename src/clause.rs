@@ -1,105 +1,1122 @@
 use crate::ast;
+use crate::tag::cowstr_into_static;
+use crate::visit::{walk_block, walk_outofband, Visitor};
+use core::ops::Deref;
+use std::collections::{HashMap, VecDeque};
+use std::sync::Arc;
+use thiserror::Error;
 
-fn generate_clauses_for_leaf_contents<'a>(
-    leaf_contents: &ast::InlineNodeList<'a>,
-    clauses: &mut ClauseList<'a>,
+#[derive(Clone, Error, Debug)]
+#[error("malformed clause file at {}:{}", .0.line, .0.column)]
+pub struct ClauseFileError(pub ast::LineColumn);
+
+/// What kind of block a clause lives in.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum ClauseKind {
+    Paragraph,
+    Heading,
+    ListItem,
+    BlockQuote,
+    TableCell,
+    Footnote,
+    CodeBlock,
+    Html,
+}
+
+impl ClauseKind {
+    const ALL: [ClauseKind; 8] = [
+        ClauseKind::Paragraph,
+        ClauseKind::Heading,
+        ClauseKind::ListItem,
+        ClauseKind::BlockQuote,
+        ClauseKind::TableCell,
+        ClauseKind::Footnote,
+        ClauseKind::CodeBlock,
+        ClauseKind::Html,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            ClauseKind::Paragraph => "paragraph",
+            ClauseKind::Heading => "heading",
+            ClauseKind::ListItem => "list item",
+            ClauseKind::BlockQuote => "blockquote",
+            ClauseKind::TableCell => "table cell",
+            ClauseKind::Footnote => "footnote",
+            ClauseKind::CodeBlock => "code block",
+            ClauseKind::Html => "html",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.iter().copied().find(|kind| kind.name() == name)
+    }
+}
+
+/// Where the clauses being generated live within the document.
+struct ClauseContext {
+    /// The level and text of each enclosing heading, outermost first.
+    headings: Vec<(u32, String)>,
+    /// The text of `headings`, shared by the clauses under them.
+    heading_path: Arc<[String]>,
+    /// The innermost list item, blockquote or footnote around the current block.
+    container_kind: Option<ClauseKind>,
+}
+
+impl ClauseContext {
+    fn update_heading_path(&mut self) {
+        self.heading_path = self.headings.iter().map(|(_, text)| text.clone()).collect();
+    }
+}
+
+/// The text of a heading as a reader sees it, without any markup.
+fn plain_text(inlines: &[ast::InlineNode<'_>], output: &mut String) {
+    for node in inlines {
+        match node {
+            ast::InlineNode::Surrounding { contents, .. } => plain_text(contents, output),
+            ast::InlineNode::Content { tag, .. } => match tag {
+                ast::ContentInlineTag::Text(s) | ast::ContentInlineTag::Code(s) => *output += s,
+                ast::ContentInlineTag::SoftBreak | ast::ContentInlineTag::HardBreak => {
+                    *output += " "
+                }
+                _ => {}
+            },
+        }
+    }
+}
+
+fn generate_clauses_for_leaf_contents<'d, 'a>(
+    leaf_contents: &'d ast::InlineNodeList<'a>,
+    kind: ClauseKind,
+    context: &ClauseContext,
+    clauses: &mut Vec<ClauseRef<'d, 'a>>,
     idx: &mut usize,
-    source_language: &pulldown_cmark::CowStr<'a>,
 ) {
     for node in leaf_contents {
-        if let ast::InlineNode::Surrounding { tag, contents } = node {
+        if let ast::InlineNode::Surrounding {
+            tag,
+            contents,
+            span,
+        } = node
+        {
             if *tag == ast::SurroundingInlineTag::Custom(crate::sentence_segment::SENTENCE_TAG) {
                 let cur_idx = *idx;
                 *idx += 1;
-                let clause = Clause {
-                    clause_contents: contents.clone(),
+                clauses.push(ClauseRef {
+                    clause_contents: contents,
                     clause_idx: cur_idx,
-                    clause_translations: vec![(source_language.clone(), contents.clone())],
-                };
-                clauses.push(clause);
+                    clause_span: span,
+                    clause_heading_path: context.heading_path.clone(),
+                    clause_kind: kind,
+                });
             }
         }
     }
 }
 
-fn generate_clauses_for_block_node<'a>(
-    block: &ast::BlockNode<'a>,
-    clauses: &mut ClauseList<'a>,
-    idx: &mut usize,
-    source_language: &pulldown_cmark::CowStr<'a>,
-) {
-    match block {
-        ast::BlockNode::Container { children, .. } => {
-            for block in children.iter() {
-                generate_clauses_for_block_node(block, clauses, idx, source_language);
-            }
+/// Collects the clauses of a document, numbering them in document order.
+struct ClauseCollector<'d, 'a> {
+    context: ClauseContext,
+    clauses: Vec<ClauseRef<'d, 'a>>,
+    idx: usize,
+}
+
+impl<'d, 'a> Visitor<'d, 'a> for ClauseCollector<'d, 'a> {
+    fn visit_document(&mut self, doc: &'d ast::Document<'a>) {
+        self.visit_blocks(&doc.blocks);
+        // footnotes are out of the flow of headings
+        self.context.headings.clear();
+        self.context.update_heading_path();
+        for outofband in &doc.outofbands {
+            self.visit_outofband(outofband);
         }
-        ast::BlockNode::Leaf { contents, .. } => {
-            generate_clauses_for_leaf_contents(contents, clauses, idx, source_language);
+    }
+
+    fn visit_block(&mut self, block: &'d ast::BlockNode<'a>) {
+        if crate::notranslate::is_untranslatable_block(block) {
+            return;
         }
-        ast::BlockNode::Markup { .. } => {
-            // do nothing
+        match block {
+            ast::BlockNode::Container { tag, .. } => {
+                let outer_kind = self.context.container_kind;
+                match tag {
+                    ast::ContainerBlockTag::BlockQuote => {
+                        self.context.container_kind = Some(ClauseKind::BlockQuote)
+                    }
+                    ast::ContainerBlockTag::ListItem => {
+                        self.context.container_kind = Some(ClauseKind::ListItem)
+                    }
+                    _ => {}
+                }
+                walk_block(self, block);
+                self.context.container_kind = outer_kind;
+            }
+            ast::BlockNode::Leaf { tag, contents, .. } => {
+                let context = &mut self.context;
+                let kind = match tag {
+                    ast::LeafBlockTag::Heading(level) => {
+                        while context
+                            .headings
+                            .last()
+                            .is_some_and(|(outer_level, _)| outer_level >= level)
+                        {
+                            let _ = context.headings.pop();
+                        }
+                        context.update_heading_path();
+                        ClauseKind::Heading
+                    }
+                    ast::LeafBlockTag::TableCell => ClauseKind::TableCell,
+                    ast::LeafBlockTag::CodeBlock(_) => ClauseKind::CodeBlock,
+                    ast::LeafBlockTag::Html => ClauseKind::Html,
+                    ast::LeafBlockTag::Paragraph | ast::LeafBlockTag::Custom(_) => {
+                        context.container_kind.unwrap_or(ClauseKind::Paragraph)
+                    }
+                };
+                generate_clauses_for_leaf_contents(
+                    contents,
+                    kind,
+                    context,
+                    &mut self.clauses,
+                    &mut self.idx,
+                );
+                if let ast::LeafBlockTag::Heading(level) = tag {
+                    let mut text = String::new();
+                    plain_text(contents, &mut text);
+                    context.headings.push((*level, text.trim().to_string()));
+                    context.update_heading_path();
+                }
+            }
+            ast::BlockNode::Markup { .. } => {
+                // do nothing
+            }
         }
     }
-}
 
-fn generate_clauses_for_out_of_band_node<'a>(
-    oob: &ast::OutOfBandNode<'a>,
-    clauses: &mut ClauseList<'a>,
-    idx: &mut usize,
-    source_language: &pulldown_cmark::CowStr<'a>,
-) {
-    match oob {
-        ast::OutOfBandNode::OutOfBandContainer { children, .. } => {
-            for block in children.iter() {
-                generate_clauses_for_block_node(block, clauses, idx, source_language);
+    fn visit_outofband(&mut self, oob: &'d ast::OutOfBandNode<'a>) {
+        self.context.container_kind = Some(ClauseKind::Footnote);
+        match oob {
+            ast::OutOfBandNode::OutOfBandContainer { .. } => walk_outofband(self, oob),
+            ast::OutOfBandNode::OutOfBandLeaf { contents, .. } => {
+                generate_clauses_for_leaf_contents(
+                    contents,
+                    ClauseKind::Footnote,
+                    &self.context,
+                    &mut self.clauses,
+                    &mut self.idx,
+                );
             }
         }
-        ast::OutOfBandNode::OutOfBandLeaf { contents, .. } => {
-            generate_clauses_for_leaf_contents(contents, clauses, idx, source_language);
-        }
     }
 }
 
 impl<'a> ast::Document<'a> {
-    pub fn extract_clause_list(
-        &self,
-        source_language: &pulldown_cmark::CowStr<'a>,
-    ) -> DocumentClauseList<'a> {
-        let mut clause_list = DocumentClauseList {
+    /// Returns a view of each clause of this segmented document, borrowing its contents
+    /// from the document.
+    pub fn clause_refs(&self) -> Vec<ClauseRef<'_, 'a>> {
+        let mut collector = ClauseCollector {
+            context: ClauseContext {
+                headings: Vec::new(),
+                heading_path: Arc::new([]),
+                container_kind: None,
+            },
             clauses: Vec::new(),
+            idx: 1,
         };
-        let mut clause_idx = 1;
-        for block in self.blocks.iter() {
-            generate_clauses_for_block_node(
-                block,
-                &mut clause_list.clauses,
-                &mut clause_idx,
-                source_language,
-            );
+        self.accept(&mut collector);
+        collector.clauses
+    }
+
+    /// Returns the clauses of this segmented document, borrowing their contents from it.
+    /// Use `DocumentClauseList::into_owned` for a list that outlives the document.
+    pub fn extract_clause_list<'d>(
+        &'d self,
+        source_language: &pulldown_cmark::CowStr<'d>,
+    ) -> DocumentClauseList<'d> {
+        DocumentClauseList {
+            clauses: self
+                .clause_refs()
+                .iter()
+                .map(|clause| clause.to_clause(source_language))
+                .collect(),
+            source_name: None,
+        }
+    }
+}
+
+/// A clause of a document, borrowing its contents from it.
+pub struct ClauseRef<'d, 'a> {
+    clause_contents: &'d ast::InlineNodeList<'a>,
+    clause_idx: usize,
+    clause_span: &'d ast::SourceSpan,
+    clause_heading_path: Arc<[String]>,
+    clause_kind: ClauseKind,
+}
+
+impl<'d, 'a> ClauseRef<'d, 'a> {
+    pub fn id(&self) -> usize {
+        self.clause_idx
+    }
+
+    pub fn contents(&self) -> &'d ast::InlineNodeList<'a> {
+        self.clause_contents
+    }
+
+    pub fn span(&self) -> &'d ast::SourceSpan {
+        self.clause_span
+    }
+
+    pub fn heading_path(&self) -> &[String] {
+        &self.clause_heading_path
+    }
+
+    pub fn kind(&self) -> ClauseKind {
+        self.clause_kind
+    }
+
+    /// Turns this clause into a `Clause` written in `source_language`, which still borrows
+    /// its contents from the document.
+    pub fn to_clause(&self, source_language: &pulldown_cmark::CowStr<'d>) -> Clause<'d> {
+        Clause {
+            clause_contents: ClauseContents::Borrowed(self.clause_contents),
+            clause_idx: self.clause_idx,
+            clause_span: self.clause_span.clone(),
+            clause_source_language: Some(source_language.clone()),
+            clause_translations: Vec::new(),
+            clause_fuzzy_languages: Vec::new(),
+            clause_machine_languages: Vec::new(),
+            clause_annotations: Vec::new(),
+            clause_notes: Vec::new(),
+            clause_heading_path: self.clause_heading_path.clone(),
+            clause_kind: self.clause_kind,
         }
+    }
+}
 
-        for outofband in self.outofbands.iter() {
-            generate_clauses_for_out_of_band_node(
-                outofband,
-                &mut clause_list.clauses,
-                &mut clause_idx,
-                source_language,
-            );
+/// The source of a clause, either borrowed from the document it was extracted from or
+/// owned, e.g. when read from a clause file.
+#[derive(Clone, Debug)]
+pub(crate) enum ClauseContents<'a> {
+    Borrowed(&'a [ast::InlineNode<'a>]),
+    Owned(ast::InlineNodeList<'a>),
+}
+
+impl<'a> ClauseContents<'a> {
+    pub(crate) fn into_list(self) -> ast::InlineNodeList<'a> {
+        match self {
+            ClauseContents::Borrowed(contents) => contents.to_vec(),
+            ClauseContents::Owned(contents) => contents,
         }
+    }
+}
+
+impl<'a> Deref for ClauseContents<'a> {
+    type Target = [ast::InlineNode<'a>];
 
-        clause_list
+    fn deref(&self) -> &Self::Target {
+        match self {
+            ClauseContents::Borrowed(contents) => contents,
+            ClauseContents::Owned(contents) => contents,
+        }
     }
 }
 
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct DocumentClauseList<'a> {
     pub(crate) clauses: ClauseList<'a>,
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_support::cowstr_option"))]
+    pub(crate) source_name: Option<pulldown_cmark::CowStr<'a>>,
+}
+
+impl<'a> DocumentClauseList<'a> {
+    /// Loads a clause list back from a parsed clause file, as written by `into_tokens`.
+    pub fn from_clause_document(doc: ast::Document<'a>) -> Result<Self, ClauseFileError> {
+        use crate::textualize::textualize_inline_list;
+
+        let mut clauses = Vec::new();
+        let mut current_clause: Option<Clause<'a>> = None;
+        let mut current_language = None;
+        let mut source_name = None;
+        let mut pending_notes = Vec::new();
+        let mut pending_context = None;
+        let mut pending_location = None;
+        let mut pending_annotations = Vec::new();
+        for block in doc.blocks {
+            let error = ClauseFileError(block.span().start);
+            match block {
+                ast::BlockNode::Container {
+                    tag: ast::ContainerBlockTag::List(Some(clause_idx)),
+                    children,
+                    ..
+                } => {
+                    clauses.extend(current_clause.take());
+                    current_language = None;
+                    let contents = children
+                        .into_iter()
+                        .find_map(|item| match item {
+                            ast::BlockNode::Container { children, .. } => {
+                                children.into_iter().find_map(|leaf| match leaf {
+                                    ast::BlockNode::Leaf { contents, .. } => Some(contents),
+                                    _ => None,
+                                })
+                            }
+                            _ => None,
+                        })
+                        .unwrap_or_default();
+                    current_clause = Some(Clause {
+                        clause_contents: ClauseContents::Owned(contents),
+                        clause_idx: clause_idx as usize,
+                        clause_span: Default::default(),
+                        clause_source_language: None,
+                        clause_translations: Vec::new(),
+                        clause_fuzzy_languages: Vec::new(),
+                        clause_machine_languages: Vec::new(),
+                        clause_annotations: core::mem::take(&mut pending_annotations),
+                        clause_notes: core::mem::take(&mut pending_notes),
+                        clause_heading_path: Arc::new([]),
+                        clause_kind: ClauseKind::Paragraph,
+                    });
+                    if let Some((kind, heading_path)) = pending_context.take() {
+                        let clause = current_clause.as_mut().unwrap();
+                        clause.clause_kind = kind;
+                        clause.clause_heading_path = heading_path;
+                    }
+                    if let Some(position) = pending_location.take() {
+                        let clause = current_clause.as_mut().unwrap();
+                        clause.clause_span = ast::SourceSpan {
+                            range: 0..0,
+                            start: position,
+                            end: position,
+                        };
+                    }
+                }
+                ast::BlockNode::Leaf {
+                    tag: ast::LeafBlockTag::Heading(3),
+                    contents,
+                    ..
+                } => {
+                    if current_clause.is_none() {
+                        return Err(error);
+                    }
+                    let mut language = String::new();
+                    textualize_inline_list(&contents, &mut language);
+                    let mut language = language.trim();
+                    let mut is_fuzzy = false;
+                    let mut is_machine_translated = false;
+                    loop {
+                        if let Some(stripped) = language.strip_suffix(FUZZY_MARKER) {
+                            is_fuzzy = true;
+                            language = stripped.trim_end();
+                        } else if let Some(stripped) =
+                            language.strip_suffix(MACHINE_TRANSLATED_MARKER)
+                        {
+                            is_machine_translated = true;
+                            language = stripped.trim_end();
+                        } else {
+                            break;
+                        }
+                    }
+                    let clause = current_clause.as_mut().ok_or(error)?;
+                    if is_fuzzy {
+                        clause
+                            .clause_fuzzy_languages
+                            .push(language.to_string().into());
+                    }
+                    if is_machine_translated {
+                        clause
+                            .clause_machine_languages
+                            .push(language.to_string().into());
+                    }
+                    current_language = Some(language.to_string());
+                }
+                ast::BlockNode::Leaf { contents, .. } => match current_language.take() {
+                    Some(language) => {
+                        let clause = current_clause.as_mut().ok_or(error)?;
+                        clause.clause_translations.push((language.into(), contents));
+                    }
+                    None => {
+                        // location, context and other comments about the next clause
+                        for node in &contents {
+                            let html = match node {
+                                ast::InlineNode::Content {
+                                    tag: ast::ContentInlineTag::RawHtml(html),
+                                    ..
+                                } => html.trim(),
+                                _ => continue,
+                            };
+                            if let Some(context) = parse_context(html) {
+                                pending_context = Some(context);
+                                continue;
+                            }
+                            let comment = match html
+                                .strip_prefix("<!--")
+                                .and_then(|html| html.strip_suffix("-->"))
+                            {
+                                Some(comment) => comment.trim(),
+                                None => continue,
+                            };
+                            if let Some((name, position)) = parse_location(comment) {
+                                if source_name.is_none() {
+                                    source_name = name.map(|name| name.to_string().into());
+                                }
+                                pending_location = Some(position);
+                            } else {
+                                pending_annotations.push(comment.to_string());
+                            }
+                        }
+                    }
+                },
+                ast::BlockNode::Markup {
+                    tag: ast::MarkupBlockTag::Rule,
+                    ..
+                } => {
+                    clauses.extend(current_clause.take());
+                    current_language = None;
+                }
+                ast::BlockNode::Container {
+                    tag: ast::ContainerBlockTag::BlockQuote,
+                    children,
+                    ..
+                } if current_language.is_none() => {
+                    // notes for the next clause
+                    for child in children {
+                        if let ast::BlockNode::Leaf { contents, .. } = child {
+                            let mut note = String::new();
+                            textualize_inline_list(&contents, &mut note);
+                            pending_notes.push(note);
+                        }
+                    }
+                }
+                _ => return Err(error),
+            }
+        }
+        clauses.extend(current_clause);
+        Ok(DocumentClauseList {
+            clauses,
+            source_name,
+        })
+    }
+
+    /// Returns an id for each clause, in order, for marking up the sentences of a rendered
+    /// document with `CustomTagRendering::IdentifiedElement`.
+    pub fn sentence_ids(&self, style: SentenceIdStyle) -> Vec<String> {
+        let mut seen_digests = HashMap::new();
+        self.clauses
+            .iter()
+            .map(|clause| match style {
+                SentenceIdStyle::ClauseIndex => clause.clause_idx.to_string(),
+                SentenceIdStyle::ContentDigest => {
+                    let digest = format!("{:016x}", fnv1a_digest(&clause.source_text()));
+                    let occurrence = seen_digests.entry(digest.clone()).or_insert(0);
+                    *occurrence += 1;
+                    if *occurrence == 1 {
+                        digest
+                    } else {
+                        format!("{}-{}", digest, occurrence)
+                    }
+                }
+            })
+            .collect()
+    }
+
+    pub(crate) fn translations_into(
+        &self,
+        language: &str,
+    ) -> HashMap<usize, &[ast::InlineNode<'a>]> {
+        self.clauses
+            .iter()
+            .filter_map(|clause| Some((clause.clause_idx, clause.translation(language)?)))
+            .collect()
+    }
+
+    /// Carries the translations of `previous`, an earlier clause list of the same document,
+    /// over to the clauses of this one.
+    ///
+    /// Each clause takes the translations of a previous clause with the same source text.
+    /// Failing that, it takes those of the previous clause following the one the clause
+    /// before it matched, if still unmatched, marked fuzzy, as it most likely is the same
+    /// clause edited. Other previous clauses are dropped.
+    pub fn merge_translations(&mut self, previous: DocumentClauseList<'a>) {
+        let mut positions_by_text: HashMap<String, VecDeque<usize>> = HashMap::new();
+        for (pos, clause) in previous.clauses.iter().enumerate() {
+            positions_by_text
+                .entry(clause.source_text())
+                .or_default()
+                .push_back(pos);
+        }
+        let mut previous: Vec<Option<Clause<'a>>> =
+            previous.clauses.into_iter().map(Some).collect();
+        // matching by text first, so that a clause edited in place cannot take a moved one
+        let exact_matches: Vec<Option<(usize, Clause<'a>)>> = self
+            .clauses
+            .iter()
+            .map(|clause| {
+                let pos = positions_by_text
+                    .get_mut(&clause.source_text())?
+                    .pop_front()?;
+                Some((pos, previous[pos].take()?))
+            })
+            .collect();
+
+        let mut next_pos = 0;
+        for (clause, exact_match) in self.clauses.iter_mut().zip(exact_matches) {
+            match exact_match {
+                Some((pos, previous_clause)) => {
+                    clause.take_translations(previous_clause, false);
+                    next_pos = pos + 1;
+                }
+                None => {
+                    if let Some(previous_clause) = previous.get_mut(next_pos).and_then(Option::take)
+                    {
+                        clause.take_translations(previous_clause, true);
+                        next_pos += 1;
+                    }
+                }
+            }
+        }
+    }
+
+    /// Copies the borrowed strings of this list, so that it outlives the source text.
+    pub fn into_owned(self) -> DocumentClauseList<'static> {
+        DocumentClauseList {
+            clauses: self.clauses.into_iter().map(Clause::into_owned).collect(),
+            source_name: self.source_name.map(cowstr_into_static),
+        }
+    }
+
+    /// Builds a clause list out of `clauses`, which should be numbered from 1 in order.
+    pub fn from_clauses(clauses: Vec<Clause<'a>>) -> Self {
+        DocumentClauseList {
+            clauses,
+            source_name: None,
+        }
+    }
+
+    pub fn iter(&self) -> core::slice::Iter<'_, Clause<'a>> {
+        self.clauses.iter()
+    }
+
+    pub fn iter_mut(&mut self) -> core::slice::IterMut<'_, Clause<'a>> {
+        self.clauses.iter_mut()
+    }
+
+    pub fn len(&self) -> usize {
+        self.clauses.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.clauses.is_empty()
+    }
+
+    /// The clause with the given id.
+    pub fn get(&self, id: usize) -> Option<&Clause<'a>> {
+        self.clauses.iter().find(|clause| clause.clause_idx == id)
+    }
+
+    pub fn get_mut(&mut self, id: usize) -> Option<&mut Clause<'a>> {
+        self.clauses
+            .iter_mut()
+            .find(|clause| clause.clause_idx == id)
+    }
+
+    pub fn source_name(&self) -> Option<&str> {
+        self.source_name.as_deref()
+    }
+
+    /// Sets the name of the source file, used when reporting clause locations.
+    pub fn set_source_name(&mut self, source_name: pulldown_cmark::CowStr<'a>) {
+        self.source_name = Some(source_name);
+    }
+}
+
+impl<'l, 'a> IntoIterator for &'l DocumentClauseList<'a> {
+    type Item = &'l Clause<'a>;
+    type IntoIter = core::slice::Iter<'l, Clause<'a>>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
 }
 
 type ClauseList<'a> = Vec<Clause<'a>>;
 
+/// Follows the language in the heading of a translation that needs review, e.g.
+/// `### zh-CN (fuzzy)`.
+pub const FUZZY_MARKER: &str = "(fuzzy)";
+
+/// Follows the language in the heading of a translation produced by a machine, e.g.
+/// `### zh-CN (machine)`.
+pub const MACHINE_TRANSLATED_MARKER: &str = "(machine)";
+
+/// How `DocumentClauseList::sentence_ids` identifies clauses.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum SentenceIdStyle {
+    /// The index of the clause within its document, as in the clause file.
+    ClauseIndex,
+    /// A digest of the source text of the clause, which survives edits elsewhere in the
+    /// document. Repeated sentences get a `-2`, `-3`, ... suffix.
+    ContentDigest,
+}
+
+/// 64-bit FNV-1a, chosen over `DefaultHasher` because its output never changes.
+fn fnv1a_digest(text: &str) -> u64 {
+    text.bytes().fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Clause<'a> {
-    pub(crate) clause_contents: ast::InlineNodeList<'a>,
+    #[cfg_attr(
+        feature = "serde",
+        serde(with = "crate::serde_support::clause_contents")
+    )]
+    pub(crate) clause_contents: ClauseContents<'a>,
     pub(crate) clause_idx: usize,
+    pub(crate) clause_span: ast::SourceSpan,
+    /// The language of `clause_contents`, which then serves as its translation into that
+    /// language unless another one is set.
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_support::cowstr_option"))]
+    pub(crate) clause_source_language: Option<pulldown_cmark::CowStr<'a>>,
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_support::translations"))]
     pub(crate) clause_translations: Vec<(pulldown_cmark::CowStr<'a>, ast::InlineNodeList<'a>)>,
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_support::cowstr_vec"))]
+    pub(crate) clause_fuzzy_languages: Vec<pulldown_cmark::CowStr<'a>>,
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_support::cowstr_vec"))]
+    pub(crate) clause_machine_languages: Vec<pulldown_cmark::CowStr<'a>>,
+    /// Notes for translators, written to the clause file as comments.
+    pub(crate) clause_annotations: Vec<String>,
+    /// Notes for translators from the source, written to the clause file as blockquotes.
+    pub(crate) clause_notes: Vec<String>,
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_support::heading_path"))]
+    pub(crate) clause_heading_path: Arc<[String]>,
+    pub(crate) clause_kind: ClauseKind,
+}
+
+// extracted clauses share their heading paths, which must not tie them to one thread
+const _: fn() = || {
+    fn assert_send<T: Send>() {}
+    assert_send::<Clause<'static>>();
+};
+
+const CONTEXT_COMMENT_PREFIX: &str = "<!-- context: ";
+
+/// Parses a location comment as written for each clause, `name:line:column` or
+/// `line:column`.
+fn parse_location(comment: &str) -> Option<(Option<&str>, ast::LineColumn)> {
+    let mut parts = comment.rsplitn(3, ':');
+    let column = parts.next()?.parse().ok()?;
+    let line = parts.next()?.parse().ok()?;
+    Some((parts.next(), ast::LineColumn { line, column }))
+}
+
+/// Parses the comment written by `Clause::context_comment`.
+fn parse_context(html: &str) -> Option<(ClauseKind, Arc<[String]>)> {
+    let context = html
+        .trim()
+        .strip_prefix(CONTEXT_COMMENT_PREFIX)?
+        .strip_suffix("-->")?
+        .trim();
+    let (kind, heading_path) = match context.split_once(", ") {
+        Some((kind, heading_path)) => (kind, heading_path.split(" > ").map(String::from).collect()),
+        None => (context, Arc::from([])),
+    };
+    Some((ClauseKind::from_name(kind)?, heading_path))
+}
+
+impl<'a> Clause<'a> {
+    /// Creates a clause with the given id and source contents, and nothing else.
+    pub fn new(id: usize, contents: ast::InlineNodeList<'a>) -> Self {
+        Clause {
+            clause_span: ast::SourceSpan::covering_inlines(&contents),
+            clause_contents: ClauseContents::Owned(contents),
+            clause_idx: id,
+            clause_source_language: None,
+            clause_translations: Vec::new(),
+            clause_fuzzy_languages: Vec::new(),
+            clause_machine_languages: Vec::new(),
+            clause_annotations: Vec::new(),
+            clause_notes: Vec::new(),
+            clause_heading_path: Arc::new([]),
+            clause_kind: ClauseKind::Paragraph,
+        }
+    }
+
+    /// Copies the borrowed strings of this clause, so that it outlives the source text.
+    pub fn into_owned(self) -> Clause<'static> {
+        let into_owned_list = |list: ast::InlineNodeList<'a>| {
+            list.into_iter().map(ast::InlineNode::into_owned).collect()
+        };
+        Clause {
+            clause_contents: ClauseContents::Owned(into_owned_list(
+                self.clause_contents.into_list(),
+            )),
+            clause_idx: self.clause_idx,
+            clause_span: self.clause_span,
+            clause_source_language: self.clause_source_language.map(cowstr_into_static),
+            clause_translations: self
+                .clause_translations
+                .into_iter()
+                .map(|(language, contents)| {
+                    (cowstr_into_static(language), into_owned_list(contents))
+                })
+                .collect(),
+            clause_fuzzy_languages: self
+                .clause_fuzzy_languages
+                .into_iter()
+                .map(cowstr_into_static)
+                .collect(),
+            clause_machine_languages: self
+                .clause_machine_languages
+                .into_iter()
+                .map(cowstr_into_static)
+                .collect(),
+            clause_annotations: self.clause_annotations,
+            clause_notes: self.clause_notes,
+            clause_heading_path: self.clause_heading_path,
+            clause_kind: self.clause_kind,
+        }
+    }
+
+    /// The 1-based index of this clause within its document, as in the clause file.
+    pub fn id(&self) -> usize {
+        self.clause_idx
+    }
+
+    /// The source text of this clause.
+    pub fn contents(&self) -> &[ast::InlineNode<'a>] {
+        &self.clause_contents
+    }
+
+    /// The plain text of the source, trimmed, for comparing clauses.
+    pub(crate) fn source_text(&self) -> String {
+        let mut text = String::new();
+        crate::textualize::textualize_inline_list(&self.clause_contents, &mut text);
+        text.trim().to_string()
+    }
+
+    /// Adds the translations of `previous` other than into the source language, with
+    /// their markers, marking each fuzzy too if `fuzzy`.
+    fn take_translations(&mut self, previous: Clause<'a>, fuzzy: bool) {
+        for (language, contents) in previous.clause_translations {
+            if self.clause_source_language.as_ref() == Some(&language)
+                || self
+                    .clause_translations
+                    .iter()
+                    .any(|(lang, _)| *lang == language)
+            {
+                continue;
+            }
+            if (fuzzy && !contents.is_empty())
+                || previous.clause_fuzzy_languages.contains(&language)
+            {
+                self.clause_fuzzy_languages.push(language.clone());
+            }
+            if previous.clause_machine_languages.contains(&language) {
+                self.clause_machine_languages.push(language.clone());
+            }
+            self.clause_translations.push((language, contents));
+        }
+    }
+
+    /// The translation into `language`, if any. The source language is listed too.
+    pub fn translation(&self, language: &str) -> Option<&[ast::InlineNode<'a>]> {
+        self.translations()
+            .find(|(lang, _)| *lang == language)
+            .map(|(_, contents)| contents)
+    }
+
+    /// The source language of this clause, unless it has a translation of its own.
+    pub(crate) fn implicit_source_language(&self) -> Option<&str> {
+        self.clause_source_language.as_deref().filter(|language| {
+            !self
+                .clause_translations
+                .iter()
+                .any(|(lang, _)| lang.as_ref() == *language)
+        })
+    }
+
+    /// Each language of this clause with its translation, in clause file order.
+    pub fn translations(&self) -> impl Iterator<Item = (&str, &[ast::InlineNode<'a>])> {
+        self.implicit_source_language()
+            .map(|language| (language, &*self.clause_contents))
+            .into_iter()
+            .chain(
+                self.clause_translations
+                    .iter()
+                    .map(|(lang, contents)| (lang.as_ref(), &contents[..])),
+            )
+    }
+
+    /// Sets the translation into `language`, replacing any previous one. Its fuzzy and
+    /// machine markers are left as they are.
+    pub fn set_translation(&mut self, language: &str, contents: ast::InlineNodeList<'a>) {
+        match self
+            .clause_translations
+            .iter_mut()
+            .find(|(lang, _)| lang.as_ref() == language)
+        {
+            Some((_, translation)) => *translation = contents,
+            None => self
+                .clause_translations
+                .push((language.to_string().into(), contents)),
+        }
+    }
+
+    /// The notes left for translators in the source.
+    pub fn notes(&self) -> &[String] {
+        &self.clause_notes
+    }
+
+    /// The other comments for translators, such as glossary hints.
+    pub fn annotations(&self) -> &[String] {
+        &self.clause_annotations
+    }
+
+    /// The text of each heading enclosing this clause, outermost first.
+    pub fn heading_path(&self) -> &[String] {
+        &self.clause_heading_path
+    }
+
+    pub fn kind(&self) -> ClauseKind {
+        self.clause_kind
+    }
+
+    /// Describes the context of this clause in a comment for the clause file, e.g.
+    /// `<!-- context: list item, Installation > Linux -->`.
+    pub(crate) fn context_comment(&self) -> String {
+        let mut comment = CONTEXT_COMMENT_PREFIX.to_string();
+        comment += self.clause_kind.name();
+        if !self.clause_heading_path.is_empty() {
+            comment += ", ";
+            comment += &self.clause_heading_path.join(" > ").replace("--", "- -");
+        }
+        comment += " -->\n";
+        comment
+    }
+
+    /// Whether the translation into `language` is marked as needing review.
+    pub fn is_fuzzy(&self, language: &str) -> bool {
+        self.clause_fuzzy_languages
+            .iter()
+            .any(|lang| lang.as_ref() == language)
+    }
+
+    /// Whether the translation into `language` was produced by a machine.
+    pub fn is_machine_translated(&self, language: &str) -> bool {
+        self.clause_machine_languages
+            .iter()
+            .any(|lang| lang.as_ref() == language)
+    }
+
+    pub fn span(&self) -> &ast::SourceSpan {
+        &self.clause_span
+    }
+
+    /// Returns the location of this clause within the source text, e.g. `42:7`,
+    /// prefixed with `source_name` when given.
+    pub fn location(&self, source_name: Option<&str>) -> String {
+        let position = &self.clause_span.start;
+        match source_name {
+            Some(name) => format!("{}:{}:{}", name, position.line, position.column),
+            None => format!("{}:{}", position.line, position.column),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{clause_list, inlines, parse, segmented, spanned, translate};
+
+    #[test]
+    fn clauses_record_their_location() {
+        let text = "One. Two\nstill two.\n\n> Three.\n";
+        let doc = segmented(text);
+        let mut clause_list = doc.extract_clause_list(&"en-US".into());
+        let spans: Vec<&str> = clause_list
+            .clauses
+            .iter()
+            .map(|clause| spanned(text, clause.span()))
+            .collect();
+        assert_eq!(spans, ["One. ", "Two\nstill two.", "Three."]);
+        let locations: Vec<String> = clause_list
+            .clauses
+            .iter()
+            .map(|clause| clause.location(None))
+            .collect();
+        assert_eq!(locations, ["1:1", "1:6", "4:3"]);
+        clause_list.set_source_name("a.md".into());
+        assert_eq!(clause_list.clauses[2].location(Some("a.md")), "a.md:4:3");
+    }
+
+    #[test]
+    fn clause_files_keep_fuzzy_markers() {
+        let clauses_text = "1. One.\n\n### en-US\n\nOne.\n\n### zh-CN (fuzzy)\n\n一。\n\n---\n";
+        let previous = DocumentClauseList::from_clause_document(parse(clauses_text)).unwrap();
+        assert!(previous.clauses[0].is_fuzzy("zh-CN"));
+        assert!(!previous.clauses[0].is_fuzzy("en-US"));
+
+        let mut clause_list = clause_list("One.\n");
+        clause_list.merge_translations(previous);
+        assert!(clause_list.clauses[0].is_fuzzy("zh-CN"));
+        let mut output_text = String::new();
+        pulldown_cmark_to_cmark::cmark(clause_list.into_tokens(), &mut output_text, None).unwrap();
+        assert!(
+            output_text.contains("### zh-CN (fuzzy)\n\n一。"),
+            "{}",
+            output_text
+        );
+    }
+
+    #[test]
+    fn clause_lists_can_be_built_by_hand() {
+        let doc = parse("One.\n");
+        let (blocks, _) = doc.clone().into_parts();
+        let contents = match &blocks[0] {
+            ast::BlockNode::Leaf { contents, .. } => contents.clone(),
+            _ => panic!("not a paragraph"),
+        };
+        let mut clause_list = DocumentClauseList::from_clauses(vec![Clause::new(1, contents)]);
+        assert_eq!(clause_list.len(), 1);
+        assert!(clause_list.get(2).is_none());
+
+        let clause = clause_list.get_mut(1).unwrap();
+        clause.set_translation("fr", inlines("Un."));
+        clause.set_translation("fr", inlines("Une."));
+        let languages: Vec<&str> = clause.translations().map(|(lang, _)| lang).collect();
+        assert_eq!(languages, ["fr"]);
+
+        let mut translated = doc;
+        translated.apply_translations(&clause_list, "fr");
+        let mut output_text = String::new();
+        pulldown_cmark_to_cmark::cmark(translated.into_tokens(), &mut output_text, None).unwrap();
+        // the document was not segmented, so it has no sentences to translate
+        assert_eq!(output_text, "One.");
+    }
+
+    #[test]
+    fn clauses_borrow_from_the_document() {
+        let doc = segmented("# Top\n\nOne. Two.\n\n## Sub\n\nThree.\n");
+        let refs = doc.clause_refs();
+        assert_eq!(refs[1].heading_path(), ["Top"]);
+        assert!(Arc::ptr_eq(
+            &refs[1].clause_heading_path,
+            &refs[2].clause_heading_path
+        ));
+        assert_eq!(refs[4].heading_path(), ["Top", "Sub"]);
+
+        let clause_list = doc.extract_clause_list(&"en-US".into());
+        assert!(clause_list.clauses.iter().zip(&refs).all(|(clause, clause_ref)| {
+            matches!(
+                clause.clause_contents,
+                ClauseContents::Borrowed(contents) if core::ptr::eq(contents, &clause_ref.contents()[..])
+            )
+        }));
+    }
+
+    #[test]
+    fn owned_clause_lists_outlive_their_source() {
+        let clause_list = {
+            let text = String::from("One. *Two*.\n");
+            let mut clause_list = clause_list(&text);
+            translate(&mut clause_list, 2, "fr", "*Deux*.");
+            clause_list.into_owned()
+        };
+        assert_eq!(clause_list.clauses[1].source_text(), "(Two).");
+        assert!(clause_list.clauses[1].translation("fr").is_some());
+    }
+
+    #[test]
+    fn clauses_know_their_headings_and_kind() {
+        let clause_list = clause_list(
+            "# Guide\n\nIntro.\n\n## Install\n\n- Step.\n\n> Quote.\n\n# Next\n\nMore.\n",
+        );
+        let contexts: Vec<(ClauseKind, String)> = clause_list
+            .clauses
+            .iter()
+            .map(|clause| (clause.kind(), clause.heading_path().join(" > ")))
+            .collect();
+        let context = |kind, path: &str| (kind, path.to_string());
+        assert_eq!(
+            contexts,
+            [
+                context(ClauseKind::Heading, ""),
+                context(ClauseKind::Paragraph, "Guide"),
+                context(ClauseKind::Heading, "Guide"),
+                context(ClauseKind::ListItem, "Guide > Install"),
+                context(ClauseKind::BlockQuote, "Guide > Install"),
+                context(ClauseKind::Heading, ""),
+                context(ClauseKind::Paragraph, "Next"),
+            ]
+        );
+    }
+
+    #[test]
+    fn clause_contexts_survive_the_clause_file() {
+        let clause_list = clause_list("# Guide\n\n## Install\n\n- Step.\n");
+        let mut clauses_text = String::new();
+        pulldown_cmark_to_cmark::cmark(clause_list.into_tokens(), &mut clauses_text, None).unwrap();
+        assert!(
+            clauses_text.contains("<!-- context: list item, Guide > Install -->"),
+            "{}",
+            clauses_text
+        );
+        let loaded = DocumentClauseList::from_clause_document(parse(&clauses_text)).unwrap();
+        let step = &loaded.clauses[2];
+        assert_eq!(step.kind(), ClauseKind::ListItem);
+        assert_eq!(step.heading_path(), ["Guide", "Install"]);
+    }
+
+    #[test]
+    fn clause_files_round_trip() {
+        let source = "<!-- translator: a note -->\n\n# Title\n\nOne. Two.\n";
+        let mut extracted = clause_list(source);
+        extracted.set_source_name("a.md".into());
+        extracted.attach_notes(&segmented(source), crate::notes::DEFAULT_NOTE_PREFIX);
+        let mut clauses_text = String::new();
+        pulldown_cmark_to_cmark::cmark(extracted.into_tokens(), &mut clauses_text, None).unwrap();
+        let clauses_text = clauses_text.replace(
+            "### en-US\n\nTwo.",
+            "### en-US\n\nTwo.\n\n### fr (machine)\n\nDeux.",
+        );
+
+        let loaded = DocumentClauseList::from_clause_document(parse(&clauses_text)).unwrap();
+        assert!(loaded.clauses[2].is_machine_translated("fr"));
+        let mut reloaded_text = String::new();
+        pulldown_cmark_to_cmark::cmark(loaded.into_tokens(), &mut reloaded_text, None).unwrap();
+        assert_eq!(reloaded_text, clauses_text);
+
+        let mut merged = clause_list(source);
+        merged.merge_translations(
+            DocumentClauseList::from_clause_document(parse(&clauses_text)).unwrap(),
+        );
+        assert!(merged.clauses[2].is_machine_translated("fr"));
+    }
+
+    #[test]
+    fn merged_translations_follow_text_then_position() {
+        let mut previous = clause_list("One. Two. Three.\n");
+        for (id, text) in [(1, "Un."), (2, "Deux."), (3, "Trois.")] {
+            translate(&mut previous, id, "fr", text);
+        }
+
+        let mut clause_list = clause_list("Three. One. Two again.\n");
+        clause_list.merge_translations(previous);
+        let translations: Vec<String> = clause_list
+            .clauses
+            .iter()
+            .map(|clause| {
+                let mut text = String::new();
+                crate::textualize::textualize_inline_list(
+                    clause.translation("fr").unwrap(),
+                    &mut text,
+                );
+                text
+            })
+            .collect();
+        assert_eq!(translations, ["Trois.", "Un.", "Deux."]);
+        assert!(!clause_list.clauses[1].is_fuzzy("fr"));
+        assert!(clause_list.clauses[2].is_fuzzy("fr"));
+    }
 }
@@ -1,14 +1,118 @@
 use crate::ast;
 use crate::tag::{
-    convert_tag, is_token_inline, ConvertedInbandTag, ConvertedOutofbandTag, ConvertedTag,
+    convert_tag, event_into_static, is_token_inline, tag_into_static, ConvertedInbandTag,
+    ConvertedOutofbandTag, ConvertedTag,
 };
 use core::mem;
+use core::ops::Range;
+use pulldown_cmark::{Event, Tag};
 use thiserror::Error;
 
+fn describe_terminator(terminator: &Option<Tag<'_>>) -> String {
+    match terminator {
+        Some(tag) => format!("end of {:?}", tag),
+        None => "no end tag".to_string(),
+    }
+}
+
+fn describe_span(span: &ast::SourceSpan) -> String {
+    format!(" at {}:{}", span.start.line, span.start.column)
+}
+
 #[derive(Clone, Error, Debug)]
-#[error("FromTokensError")]
-pub struct FromTokensError;
+pub enum FromTokensError {
+    #[error(
+        "unexpected end tag {event:?}, expected {}{}",
+        describe_terminator(.expected),
+        describe_span(.span)
+    )]
+    UnexpectedEndTag {
+        event: Box<Event<'static>>,
+        expected: Option<Tag<'static>>,
+        span: ast::SourceSpan,
+    },
+    #[error("inline event {event:?} in block context{}", describe_span(.span))]
+    InlineInBlockContext {
+        event: Box<Event<'static>>,
+        span: ast::SourceSpan,
+    },
+    #[error("block event {event:?} in inline context{}", describe_span(.span))]
+    BlockInInlineContext {
+        event: Box<Event<'static>>,
+        span: ast::SourceSpan,
+    },
+    #[error("event {event:?} inside an inline without children{}", describe_span(.span))]
+    EventInChildlessContext {
+        event: Box<Event<'static>>,
+        span: ast::SourceSpan,
+    },
+    #[error("premature end of input, expected {}", describe_terminator(.expected))]
+    PrematureEndOfInput { expected: Option<Tag<'static>> },
+    #[error("event {event:?} after the end of the document{}", describe_span(.span))]
+    TrailingEvent {
+        event: Box<Event<'static>>,
+        span: ast::SourceSpan,
+    },
+}
+
+impl FromTokensError {
+    /// Returns the location in the source text the error was detected at, if known.
+    pub fn span(&self) -> Option<&ast::SourceSpan> {
+        match self {
+            FromTokensError::UnexpectedEndTag { span, .. }
+            | FromTokensError::InlineInBlockContext { span, .. }
+            | FromTokensError::BlockInInlineContext { span, .. }
+            | FromTokensError::EventInChildlessContext { span, .. }
+            | FromTokensError::TrailingEvent { span, .. } => Some(span),
+            FromTokensError::PrematureEndOfInput { .. } => None,
+        }
+    }
+}
+
+type SpannedEvent<'a> = (Event<'a>, Range<usize>);
 
+struct LineIndex<'s> {
+    source: &'s str,
+    line_starts: Vec<usize>,
+}
+
+impl<'s> LineIndex<'s> {
+    fn new(source: &'s str) -> Self {
+        let line_starts = core::iter::once(0)
+            .chain(source.match_indices('\n').map(|(idx, _)| idx + 1))
+            .collect();
+        LineIndex {
+            source,
+            line_starts,
+        }
+    }
+
+    fn line_column(&self, offset: usize) -> ast::LineColumn {
+        let line_idx = match self.line_starts.binary_search(&offset) {
+            Ok(idx) => idx,
+            Err(idx) => idx - 1,
+        };
+        let line_start = self.line_starts[line_idx];
+        let column = self
+            .source
+            .get(line_start..offset)
+            .map_or(offset - line_start, |s| s.chars().count());
+        ast::LineColumn {
+            line: line_idx + 1,
+            column: column + 1,
+        }
+    }
+
+    fn span(&self, range: Range<usize>) -> ast::SourceSpan {
+        ast::SourceSpan {
+            start: self.line_column(range.start),
+            end: self.line_column(range.end),
+            range,
+        }
+    }
+}
+
+#[allow(clippy::enum_variant_names)]
 enum InBandContext<'a, 'b> {
     ChildrenBlocks {
         blocks: &'b mut ast::BlockNodeList<'a>,
@@ -19,6 +123,23 @@ enum InBandContext<'a, 'b> {
     ChildrenNone {},
 }
 
+impl<'a, 'b> InBandContext<'a, 'b> {
+    fn misplaced_event_error(&self, event: Event<'_>, span: ast::SourceSpan) -> FromTokensError {
+        let event = Box::new(event_into_static(event));
+        match self {
+            InBandContext::ChildrenBlocks { .. } => {
+                FromTokensError::InlineInBlockContext { event, span }
+            }
+            InBandContext::ChildrenInlines { .. } => {
+                FromTokensError::BlockInInlineContext { event, span }
+            }
+            InBandContext::ChildrenNone {} => {
+                FromTokensError::EventInChildlessContext { event, span }
+            }
+        }
+    }
+}
+
 enum OutOfBandContext<'a, 'b> {
     OutOfBand {
         outofbands: &'b mut ast::OutOfBandNodeList<'a>,
@@ -36,24 +157,29 @@ impl<'a, 'b> OutOfBandContext<'a, 'b> {
 }
 
 fn read_token_or_token_region<'a>(
-    tokens: &mut core::iter::Peekable<impl Iterator<Item = pulldown_cmark::Event<'a>>>,
-    output: &mut Vec<pulldown_cmark::Event<'a>>,
+    tokens: &mut core::iter::Peekable<impl Iterator<Item = SpannedEvent<'a>>>,
+    output: &mut Vec<SpannedEvent<'a>>,
+    line_index: &LineIndex<'_>,
 ) -> Result<(), FromTokensError> {
     let mut region_stack = Vec::new();
-    while let Some(token) = tokens.next() {
+    for (token, range) in tokens.by_ref() {
         match &token {
-            pulldown_cmark::Event::Start(tag) => {
+            Event::Start(tag) => {
                 region_stack.push(tag.clone());
             }
-            pulldown_cmark::Event::End(tag) => {
+            Event::End(tag) => {
                 let stack_item = region_stack.pop();
                 if stack_item.as_ref() != Some(tag) {
-                    return Err(FromTokensError);
+                    return Err(FromTokensError::UnexpectedEndTag {
+                        event: Box::new(event_into_static(token)),
+                        expected: stack_item.map(tag_into_static),
+                        span: line_index.span(range),
+                    });
                 }
             }
             _ => {}
         }
-        output.push(token);
+        output.push((token, range));
         if region_stack.is_empty() {
             return Ok(());
         }
@@ -62,37 +188,36 @@ fn read_token_or_token_region<'a>(
 }
 
 fn load_ast_nodes<'a, 'b>(
-    tokens: &mut core::iter::Peekable<impl Iterator<Item = pulldown_cmark::Event<'a>>>,
-    terminator: Option<pulldown_cmark::Tag<'a>>,
+    tokens: &mut core::iter::Peekable<impl Iterator<Item = SpannedEvent<'a>>>,
+    terminator: Option<Tag<'a>>,
     mut in_band_ctx: InBandContext<'a, 'b>,
     mut out_of_band_ctx: OutOfBandContext<'a, 'b>,
+    line_index: &LineIndex<'_>,
 ) -> Result<(), FromTokensError> {
-    use pulldown_cmark::Event;
-
     loop {
-        let mut peek_token = tokens.peek();
+        let peek_token = tokens.peek().map(|(token, _)| token);
         if peek_token.is_none() {
             if terminator.is_none() {
                 break;
             } else {
-                return Err(FromTokensError);
+                return Err(FromTokensError::PrematureEndOfInput {
+                    expected: terminator.map(tag_into_static),
+                });
             }
         }
-        if let (
-            Some(Event::Start(pulldown_cmark::Tag::Item)),
-            InBandContext::ChildrenBlocks { blocks },
-        ) = (&peek_token, &mut in_band_ctx)
+        if let (Some(Event::Start(Tag::Item)), InBandContext::ChildrenBlocks { blocks }) =
+            (&peek_token, &mut in_band_ctx)
         {
             // Workaround https://github.com/raphlinus/pulldown-cmark/issues/475
             let mut children = Vec::new();
             let mut temporary_inline_contents = Vec::new();
-            let _ = tokens.next();
+            let item_span = line_index.span(tokens.next().unwrap().1);
             'specialized_processing_item_node: loop {
-                let peek_token = tokens.peek();
+                let peek_token = tokens.peek().map(|(token, _)| token);
                 if let Some(token) = peek_token {
-                    if is_token_inline(&token) {
+                    if is_token_inline(token) {
                         let mut temporary_buffer = Vec::new();
-                        read_token_or_token_region(tokens, &mut temporary_buffer)?;
+                        read_token_or_token_region(tokens, &mut temporary_buffer, line_index)?;
                         let inner_in_band_ctx = InBandContext::ChildrenInlines {
                             inlines: &mut temporary_inline_contents,
                         };
@@ -101,25 +226,32 @@ fn load_ast_nodes<'a, 'b>(
                             None,
                             inner_in_band_ctx,
                             out_of_band_ctx.reborrow(),
+                            line_index,
                         )?;
                     } else {
                         if !temporary_inline_contents.is_empty() {
                             let paragraph_contents = mem::take(&mut temporary_inline_contents);
                             let paragraph = ast::BlockNode::Leaf {
                                 tag: ast::LeafBlockTag::Paragraph,
+                                span: ast::SourceSpan::covering_inlines(&paragraph_contents),
                                 contents: paragraph_contents,
                             };
                             children.push(paragraph);
                         }
                         if let Some(Event::End(t)) = peek_token {
-                            if !matches!(t, pulldown_cmark::Tag::Item) {
-                                return Err(FromTokensError);
+                            if !matches!(t, Tag::Item) {
+                                let (event, range) = tokens.next().unwrap();
+                                return Err(FromTokensError::UnexpectedEndTag {
+                                    event: Box::new(event_into_static(event)),
+                                    expected: Some(Tag::Item),
+                                    span: line_index.span(range),
+                                });
                             }
                             let _ = tokens.next();
                             break 'specialized_processing_item_node;
                         }
                         let mut temporary_buffer = Vec::new();
-                        read_token_or_token_region(tokens, &mut temporary_buffer)?;
+                        read_token_or_token_region(tokens, &mut temporary_buffer, line_index)?;
                         let inner_in_band_ctx = InBandContext::ChildrenBlocks {
                             blocks: &mut children,
                         };
@@ -128,15 +260,19 @@ fn load_ast_nodes<'a, 'b>(
                             None,
                             inner_in_band_ctx,
                             out_of_band_ctx.reborrow(),
+                            line_index,
                         )?;
                     }
                 } else {
-                    return Err(FromTokensError);
+                    return Err(FromTokensError::PrematureEndOfInput {
+                        expected: Some(Tag::Item),
+                    });
                 }
             }
             let item_block = ast::BlockNode::Container {
                 tag: ast::ContainerBlockTag::ListItem,
                 children,
+                span: item_span,
             };
             blocks.push(item_block);
             continue;
@@ -144,23 +280,32 @@ fn load_ast_nodes<'a, 'b>(
             (&peek_token, &mut in_band_ctx)
         {
             // Workaround https://github.com/raphlinus/pulldown-cmark/issues/473
+            // consecutive HTML blocks are told apart by the gap between them
             let mut contents = Vec::new();
-            while let Some(Event::Html(..)) = peek_token {
-                if let Some(Event::Html(s)) = tokens.next() {
+            let mut last_end = None;
+            while let Some((Event::Html(..), range)) = tokens.peek() {
+                if last_end.is_some_and(|end| end != range.start) {
+                    break;
+                }
+                if let Some((Event::Html(s), range)) = tokens.next() {
+                    last_end = Some(range.end);
                     contents.push(ast::InlineNode::Content {
                         tag: ast::ContentInlineTag::RawHtml(s),
+                        span: line_index.span(range),
                     });
                 }
-                peek_token = tokens.peek();
             }
             let html_block = ast::BlockNode::Leaf {
                 tag: ast::LeafBlockTag::Html,
+                span: ast::SourceSpan::covering_inlines(&contents),
                 contents,
             };
             blocks.push(html_block);
             continue;
         }
-        match tokens.next().unwrap() {
+        let (token, range) = tokens.next().unwrap();
+        let span = line_index.span(range);
+        match token {
             Event::Start(tag) => match convert_tag(tag.clone()) {
                 ConvertedTag::InBand(in_band_tag) => match &mut in_band_ctx {
                     InBandContext::ChildrenBlocks { blocks } => {
@@ -170,15 +315,21 @@ fn load_ast_nodes<'a, 'b>(
                                 new_inband_node = ast::BlockNode::Container {
                                     tag,
                                     children: Vec::new(),
+                                    span,
                                 };
                             }
                             ConvertedInbandTag::LeafBlock(tag) => {
                                 new_inband_node = ast::BlockNode::Leaf {
                                     tag,
                                     contents: Vec::new(),
+                                    span,
                                 };
                             }
-                            _ => return Err(FromTokensError),
+                            _ => {
+                                return Err(
+                                    in_band_ctx.misplaced_event_error(Event::Start(tag), span)
+                                )
+                            }
                         }
                         let inner_ib_ctx = match &mut new_inband_node {
                             ast::BlockNode::Container { children, .. } => {
@@ -194,6 +345,7 @@ fn load_ast_nodes<'a, 'b>(
                             Some(tag),
                             inner_ib_ctx,
                             out_of_band_ctx.reborrow(),
+                            line_index,
                         )?;
                         blocks.push(new_inband_node);
                     }
@@ -204,12 +356,17 @@ fn load_ast_nodes<'a, 'b>(
                                 new_inband_node = ast::InlineNode::Surrounding {
                                     tag,
                                     contents: Vec::new(),
+                                    span,
                                 };
                             }
                             ConvertedInbandTag::ContentInline(tag) => {
-                                new_inband_node = ast::InlineNode::Content { tag };
+                                new_inband_node = ast::InlineNode::Content { tag, span };
+                            }
+                            _ => {
+                                return Err(
+                                    in_band_ctx.misplaced_event_error(Event::Start(tag), span)
+                                )
                             }
-                            _ => return Err(FromTokensError),
                         }
                         let inner_ib_ctx = match &mut new_inband_node {
                             ast::InlineNode::Surrounding { contents, .. } => {
@@ -222,11 +379,12 @@ fn load_ast_nodes<'a, 'b>(
                             Some(tag),
                             inner_ib_ctx,
                             out_of_band_ctx.reborrow(),
+                            line_index,
                         )?;
                         inlines.push(new_inband_node);
                     }
                     InBandContext::ChildrenNone {} => {
-                        return Err(FromTokensError);
+                        return Err(in_band_ctx.misplaced_event_error(Event::Start(tag), span));
                     }
                 },
                 ConvertedTag::OutOfBand(out_of_band_tag) => {
@@ -241,6 +399,7 @@ fn load_ast_nodes<'a, 'b>(
                             new_oob_node = ast::OutOfBandNode::OutOfBandContainer {
                                 tag: out_of_band_tag,
                                 children: Vec::new(),
+                                span,
                             };
                         }
                         ConvertedOutofbandTag::OutOfBandLeaf(tag) => {
@@ -248,6 +407,7 @@ fn load_ast_nodes<'a, 'b>(
                             new_oob_node = ast::OutOfBandNode::OutOfBandLeaf {
                                 tag: out_of_band_tag,
                                 contents: Vec::new(),
+                                span,
                             };
                         }
                     }
@@ -259,7 +419,7 @@ fn load_ast_nodes<'a, 'b>(
                             InBandContext::ChildrenInlines { inlines: contents }
                         }
                     };
-                    load_ast_nodes(tokens, Some(tag), inner_ib_ctx, inner_oob_ctx)?;
+                    load_ast_nodes(tokens, Some(tag), inner_ib_ctx, inner_oob_ctx, line_index)?;
                     let existing_oob_list = match &mut out_of_band_ctx {
                         OutOfBandContext::OutOfBand { outofbands } => outofbands,
                     };
@@ -268,97 +428,232 @@ fn load_ast_nodes<'a, 'b>(
                 }
             },
             Event::End(tag) => {
-                if let Some(expected_tag) = &terminator {
-                    if *expected_tag == tag {
-                        break;
-                    } else {
-                        return Err(FromTokensError);
-                    }
-                } else {
-                    return Err(FromTokensError);
+                if terminator.as_ref() == Some(&tag) {
+                    break;
                 }
+                return Err(FromTokensError::UnexpectedEndTag {
+                    event: Box::new(event_into_static(Event::End(tag))),
+                    expected: terminator.map(tag_into_static),
+                    span,
+                });
             }
             Event::Rule => match &mut in_band_ctx {
                 InBandContext::ChildrenBlocks { blocks } => blocks.push(ast::BlockNode::Markup {
                     tag: ast::MarkupBlockTag::Rule,
+                    span,
                 }),
-                _ => return Err(FromTokensError),
+                _ => return Err(in_band_ctx.misplaced_event_error(Event::Rule, span)),
             },
             Event::Text(s) => match &mut in_band_ctx {
                 InBandContext::ChildrenInlines { inlines } => {
                     inlines.push(ast::InlineNode::Content {
                         tag: ast::ContentInlineTag::Text(s),
+                        span,
                     })
                 }
-                _ => return Err(FromTokensError),
+                _ => return Err(in_band_ctx.misplaced_event_error(Event::Text(s), span)),
             },
             Event::Code(s) => match &mut in_band_ctx {
                 InBandContext::ChildrenInlines { inlines } => {
                     inlines.push(ast::InlineNode::Content {
                         tag: ast::ContentInlineTag::Code(s),
+                        span,
                     })
                 }
-                _ => return Err(FromTokensError),
+                _ => return Err(in_band_ctx.misplaced_event_error(Event::Code(s), span)),
             },
             Event::Html(s) => match &mut in_band_ctx {
                 InBandContext::ChildrenInlines { inlines } => {
                     inlines.push(ast::InlineNode::Content {
                         tag: ast::ContentInlineTag::RawHtml(s),
+                        span,
                     })
                 }
-                _ => unreachable!(),
+                _ => return Err(in_band_ctx.misplaced_event_error(Event::Html(s), span)),
             },
             Event::FootnoteReference(s) => match &mut in_band_ctx {
                 InBandContext::ChildrenInlines { inlines } => {
                     inlines.push(ast::InlineNode::Content {
                         tag: ast::ContentInlineTag::FootnoteRef(s),
+                        span,
                     })
                 }
-                _ => return Err(FromTokensError),
+                _ => {
+                    return Err(in_band_ctx.misplaced_event_error(Event::FootnoteReference(s), span))
+                }
             },
             Event::SoftBreak => match &mut in_band_ctx {
                 InBandContext::ChildrenInlines { inlines } => {
                     inlines.push(ast::InlineNode::Content {
                         tag: ast::ContentInlineTag::SoftBreak,
+                        span,
                     })
                 }
-                _ => return Err(FromTokensError),
+                _ => return Err(in_band_ctx.misplaced_event_error(Event::SoftBreak, span)),
             },
             Event::HardBreak => match &mut in_band_ctx {
                 InBandContext::ChildrenInlines { inlines } => {
                     inlines.push(ast::InlineNode::Content {
                         tag: ast::ContentInlineTag::HardBreak,
+                        span,
                     })
                 }
-                _ => return Err(FromTokensError),
+                _ => return Err(in_band_ctx.misplaced_event_error(Event::HardBreak, span)),
             },
             Event::TaskListMarker(s) => match &mut in_band_ctx {
                 InBandContext::ChildrenInlines { inlines } => {
                     inlines.push(ast::InlineNode::Content {
                         tag: ast::ContentInlineTag::TaskListMarker(s),
+                        span,
                     })
                 }
-                _ => return Err(FromTokensError),
+                _ => return Err(in_band_ctx.misplaced_event_error(Event::TaskListMarker(s), span)),
             },
         }
     }
     Ok(())
 }
 
-pub fn cmark_ast_from_tokens(
-    tokens: pulldown_cmark::Parser<'_>,
-) -> Result<ast::Document<'_>, FromTokensError> {
+/// Builds the AST from the events of `tokens`, which must be parsing `source`.
+///
+/// `source` is used to attach byte ranges and line/column positions to every node.
+pub fn cmark_ast_from_tokens<'a>(
+    tokens: pulldown_cmark::Parser<'a>,
+    source: &str,
+) -> Result<ast::Document<'a>, FromTokensError> {
     let mut doc: ast::Document = Default::default();
-    let mut tokens = tokens.peekable();
+    let line_index = LineIndex::new(source);
+    let mut tokens = tokens.into_offset_iter().peekable();
     let in_band_ctx = InBandContext::ChildrenBlocks {
         blocks: &mut doc.blocks,
     };
     let out_of_band_ctx = OutOfBandContext::OutOfBand {
         outofbands: &mut doc.outofbands,
     };
-    load_ast_nodes(&mut tokens, None, in_band_ctx, out_of_band_ctx)?;
-    if tokens.next().is_some() {
-        return Err(FromTokensError);
+    load_ast_nodes(&mut tokens, None, in_band_ctx, out_of_band_ctx, &line_index)?;
+    if let Some((event, range)) = tokens.next() {
+        return Err(FromTokensError::TrailingEvent {
+            event: Box::new(event_into_static(event)),
+            span: line_index.span(range),
+        });
     }
     Ok(doc)
 }
+
+impl<'a> ast::Document<'a> {
+    /// Parses `text` with the extensions enabled in `options`.
+    pub fn from_markdown(
+        text: &'a str,
+        options: pulldown_cmark::Options,
+    ) -> Result<Self, FromTokensError> {
+        cmark_ast_from_tokens(pulldown_cmark::Parser::new_ext(text, options), text)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{parse, spanned};
+
+    fn load<'a>(
+        events: Vec<SpannedEvent<'a>>,
+        in_band_ctx: InBandContext<'a, '_>,
+        source: &str,
+    ) -> Result<(), FromTokensError> {
+        let mut outofbands = Vec::new();
+        load_ast_nodes(
+            &mut events.into_iter().peekable(),
+            None,
+            in_band_ctx,
+            OutOfBandContext::OutOfBand {
+                outofbands: &mut outofbands,
+            },
+            &LineIndex::new(source),
+        )
+    }
+
+    #[test]
+    fn nodes_record_their_source_spans() {
+        let text = "# Title\n\nSome *emphasis*\nhere.\n\n- item\n";
+        let at = |line, column| ast::LineColumn { line, column };
+        let doc = parse(text);
+        assert_eq!(doc.blocks.len(), 3);
+        assert_eq!(spanned(text, doc.blocks[0].span()), "# Title\n");
+
+        let paragraph = &doc.blocks[1];
+        assert_eq!(spanned(text, paragraph.span()), "Some *emphasis*\nhere.\n");
+        let contents = match paragraph {
+            ast::BlockNode::Leaf { contents, .. } => contents,
+            _ => panic!("not a paragraph"),
+        };
+        let emphasis = contents[1].span();
+        assert_eq!(spanned(text, emphasis), "*emphasis*");
+        assert_eq!(emphasis.start, at(3, 6));
+        assert_eq!(emphasis.end, at(3, 16));
+        let here = contents.last().unwrap().span();
+        assert_eq!(here.start, at(4, 1));
+
+        let item = match &doc.blocks[2] {
+            ast::BlockNode::Container { children, .. } => &children[0],
+            _ => panic!("not a list"),
+        };
+        assert_eq!(spanned(text, item.span()), "- item\n");
+        assert_eq!(item.span().start, at(6, 1));
+    }
+
+    #[test]
+    fn html_blocks_are_split_at_blank_lines() {
+        let text = "<div>\nline\n</div>\n\n<!-- note -->\n<p>same block</p>\n";
+        let doc = parse(text);
+        let html: Vec<&str> = doc
+            .blocks
+            .iter()
+            .map(|block| match block {
+                ast::BlockNode::Leaf {
+                    tag: ast::LeafBlockTag::Html,
+                    span,
+                    ..
+                } => spanned(text, span),
+                _ => panic!("not an HTML block"),
+            })
+            .collect();
+        assert_eq!(
+            html,
+            [
+                "<div>\nline\n</div>\n",
+                "<!-- note -->\n<p>same block</p>\n"
+            ]
+        );
+    }
+
+    #[test]
+    fn html_without_children_is_an_error() {
+        let source = "x\n<b>";
+        let events = vec![(Event::Html("<b>".into()), 2..5)];
+        let error = load(events, InBandContext::ChildrenNone {}, source).unwrap_err();
+        assert!(matches!(
+            error,
+            FromTokensError::EventInChildlessContext { .. }
+        ));
+        assert_eq!(error.span().unwrap().start.line, 2);
+        assert!(error.to_string().ends_with(" at 2:1"));
+    }
+
+    #[test]
+    fn unexpected_end_tags_are_errors() {
+        let mut blocks = Vec::new();
+        let events = vec![(Event::End(Tag::Paragraph), 0..1)];
+        let error = load(
+            events,
+            InBandContext::ChildrenBlocks {
+                blocks: &mut blocks,
+            },
+            "x",
+        )
+        .unwrap_err();
+        assert!(matches!(
+            error,
+            FromTokensError::UnexpectedEndTag { expected: None, .. }
+        ));
+    }
+}
@@ -0,0 +1,260 @@
+use crate::clause;
+use crate::textualize::plain_text;
+use core::fmt;
+use thiserror::Error;
+
+#[derive(Clone, Error, Debug)]
+#[error("malformed glossary at line {line}: {reason}")]
+pub struct GlossaryError {
+    pub line: usize,
+    pub reason: &'static str,
+}
+
+/// The layout of a glossary file, one term per line: the source term, then the target
+/// term, which is left empty when the term must never be translated. Blank lines and
+/// lines starting with `#` are skipped.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum GlossaryFormat {
+    /// Tab-separated columns.
+    Tsv,
+    /// Comma-separated columns, which may be double-quoted.
+    Csv,
+}
+
+impl GlossaryFormat {
+    /// Guesses the format from a file name, `.csv` or anything else for TSV.
+    pub fn from_file_name(name: &str) -> Self {
+        if name.to_ascii_lowercase().ends_with(".csv") {
+            GlossaryFormat::Csv
+        } else {
+            GlossaryFormat::Tsv
+        }
+    }
+}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct GlossaryEntry {
+    pub source: String,
+    /// `None` for a term that must be kept as is.
+    pub target: Option<String>,
+}
+
+impl GlossaryEntry {
+    /// The text a translation must contain when its source contains the term.
+    pub fn required_target(&self) -> &str {
+        self.target.as_deref().unwrap_or(&self.source)
+    }
+}
+
+impl fmt::Display for GlossaryEntry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.target {
+            Some(target) => write!(f, "{} → {}", self.source, target),
+            None => write!(f, "{} (do not translate)", self.source),
+        }
+    }
+}
+
+/// The terms of one language pair.
+#[derive(Clone, Default, Debug)]
+pub struct Glossary {
+    pub entries: Vec<GlossaryEntry>,
+}
+
+fn split_csv_line(line: &str, line_number: usize) -> Result<Vec<String>, GlossaryError> {
+    let mut fields = Vec::new();
+    let mut field = String::new();
+    let mut chars = line.chars().peekable();
+    let mut in_quotes = false;
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if in_quotes => {
+                if chars.peek() == Some(&'"') {
+                    field.push('"');
+                    let _ = chars.next();
+                } else {
+                    in_quotes = false;
+                }
+            }
+            '"' if field.trim().is_empty() => {
+                field.clear();
+                in_quotes = true;
+            }
+            ',' if !in_quotes => fields.push(core::mem::take(&mut field)),
+            c => field.push(c),
+        }
+    }
+    if in_quotes {
+        return Err(GlossaryError {
+            line: line_number,
+            reason: "unterminated quoted field",
+        });
+    }
+    fields.push(field);
+    Ok(fields)
+}
+
+/// Whether a match may touch `c` without being part of a longer word. CJK text has no
+/// spaces, so its characters count as boundaries.
+fn is_word_boundary(c: Option<char>) -> bool {
+    match c {
+        Some(c) => !c.is_alphanumeric() || crate::stats::is_cjk_word_char(c),
+        None => true,
+    }
+}
+
+/// Finds `term` in `text`, ignoring case and, for terms made of words, requiring it not
+/// to be part of a longer word.
+fn contains_term(text: &str, term: &str) -> bool {
+    let text = text.to_lowercase();
+    let term = term.to_lowercase();
+    if term.is_empty() {
+        return false;
+    }
+    let check_start = term.chars().next().is_some_and(char::is_alphanumeric);
+    let check_end = term.chars().last().is_some_and(char::is_alphanumeric);
+    text.match_indices(&term).any(|(pos, _)| {
+        (!check_start || is_word_boundary(text[..pos].chars().last()))
+            && (!check_end || is_word_boundary(text[pos + term.len()..].chars().next()))
+    })
+}
+
+impl Glossary {
+    pub fn parse(text: &str, format: GlossaryFormat) -> Result<Self, GlossaryError> {
+        let mut entries = Vec::new();
+        for (idx, line) in text.lines().enumerate() {
+            let line_number = idx + 1;
+            if line.trim().is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let fields = match format {
+                GlossaryFormat::Tsv => line.split('\t').map(str::to_string).collect(),
+                GlossaryFormat::Csv => split_csv_line(line, line_number)?,
+            };
+            let mut fields = fields.iter().map(|field| field.trim());
+            let source = fields.next().unwrap_or_default();
+            if source.is_empty() {
+                return Err(GlossaryError {
+                    line: line_number,
+                    reason: "missing source term",
+                });
+            }
+            let target = fields.next().filter(|target| !target.is_empty());
+            entries.push(GlossaryEntry {
+                source: source.to_string(),
+                target: target.map(str::to_string),
+            });
+        }
+        Ok(Glossary { entries })
+    }
+
+    /// Returns the entries whose source term occurs in `source_text`.
+    pub fn matches<'g>(&'g self, source_text: &str) -> Vec<&'g GlossaryEntry> {
+        self.entries
+            .iter()
+            .filter(|entry| contains_term(source_text, &entry.source))
+            .collect()
+    }
+}
+
+/// A translation missing the term the glossary requires.
+#[derive(Clone, Debug)]
+pub struct GlossaryViolation {
+    pub clause_idx: usize,
+    pub entry: GlossaryEntry,
+}
+
+impl fmt::Display for GlossaryViolation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "clause {}: expected `{}` for `{}`",
+            self.clause_idx,
+            self.entry.required_target(),
+            self.entry.source
+        )
+    }
+}
+
+impl<'a> clause::DocumentClauseList<'a> {
+    /// Checks each translation into `language` against the glossary terms found in the
+    /// source of its clause. Untranslated clauses are skipped.
+    pub fn check_glossary(&self, glossary: &Glossary, language: &str) -> Vec<GlossaryViolation> {
+        let mut violations = Vec::new();
+        for clause in &self.clauses {
+            let translation = match clause.translation(language) {
+                Some(translation) => translation,
+                None => continue,
+            };
+            let source_text = plain_text(&clause.clause_contents);
+            let translated_text = plain_text(translation);
+            for entry in glossary.matches(&source_text) {
+                if !contains_term(&translated_text, entry.required_target()) {
+                    violations.push(GlossaryViolation {
+                        clause_idx: clause.clause_idx,
+                        entry: entry.clone(),
+                    });
+                }
+            }
+        }
+        violations
+    }
+
+    /// Lists the glossary terms found in each clause in the clause file, for translators.
+    pub fn annotate_glossary(&mut self, glossary: &Glossary) {
+        for clause in self.clauses.iter_mut() {
+            let source_text = plain_text(&clause.clause_contents);
+            let hits: Vec<String> = glossary
+                .matches(&source_text)
+                .iter()
+                .map(|entry| entry.to_string())
+                .collect();
+            if !hits.is_empty() {
+                clause
+                    .clause_annotations
+                    .push(format!("glossary: {}", hits.join("; ")));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{clause_list, translate};
+
+    fn glossary() -> Glossary {
+        Glossary::parse("link\t链接\ncode\t代码\nguide\t指南\n", GlossaryFormat::Tsv).unwrap()
+    }
+
+    #[test]
+    fn terms_match_whole_words() {
+        assert!(contains_term("Read the Guide.", "guide"));
+        assert!(!contains_term("Read the guidelines.", "guide"));
+        assert!(contains_term("阅读指南。", "指南"));
+    }
+
+    #[test]
+    fn placeholders_are_not_terms() {
+        let mut clause_list = clause_list("See [the guide](https://example.com) and `main`.\n");
+        clause_list.annotate_glossary(&glossary());
+        assert_eq!(
+            clause_list.clauses[0].clause_annotations,
+            vec!["glossary: guide → 指南".to_string()]
+        );
+    }
+
+    #[test]
+    fn translations_missing_terms_are_reported() {
+        let source = "See [the guide](https://example.com).\n";
+        let mut missing = clause_list(source);
+        translate(&mut missing, 1, "zh-CN", "见[说明](https://example.com)。");
+        let violations = missing.check_glossary(&glossary(), "zh-CN");
+        assert_eq!(violations.len(), 1);
+        assert_eq!(violations[0].entry.source, "guide");
+
+        let mut present = clause_list(source);
+        translate(&mut present, 1, "zh-CN", "见[指南](https://example.com)。");
+        assert!(present.check_glossary(&glossary(), "zh-CN").is_empty());
+    }
+}
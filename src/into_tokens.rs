@@ -3,260 +3,651 @@ use crate::clause;
 use crate::tag::{
     unconvert_tag, ConvertedInbandTag, ConvertedOutofbandTag, ConvertedTag, UnconvertedTag,
 };
-use alloc::collections::VecDeque;
+use alloc::vec;
+use core::mem;
+use std::collections::HashMap;
 
-impl<'a> ast::Document<'a> {
-    pub fn into_tokens(self) -> EventIter<'a> {
-        let iter1 = self.blocks.into_iter().map(EventIterItem::Block);
-        let iter2 = self.outofbands.into_iter().map(EventIterItem::OutOfBand);
-        EventIter {
-            items: iter1.chain(iter2).collect(),
+/// How the nodes carrying a `Custom` tag are turned into `pulldown_cmark::Event`s.
+#[derive(Clone, PartialEq, Debug)]
+pub enum CustomTagRendering {
+    /// Emit the children of the node only.
+    Transparent,
+    /// Emit the children of the node between two pieces of raw HTML.
+    RawHtml { start: String, end: String },
+    /// Emit the children of the node between `<!-- name -->` and `<!-- /name -->`.
+    HtmlComment,
+    /// Emit the children of the node inside `<span attribute="id">` (or `<div>` for blocks),
+    /// taking the ids from `ids` in document order and numbering any further nodes from 1.
+    IdentifiedElement { attribute: String, ids: Vec<String> },
+    /// Emit nothing for the node and its children.
+    Omit,
+}
+
+/// Decides how each `Custom` tag is rendered, by name.
+///
+/// Tags that are not registered use the fallback rendering, which is
+/// `CustomTagRendering::Transparent` unless changed.
+#[derive(Clone, Debug)]
+pub struct CustomTagRegistry {
+    renderings: HashMap<String, CustomTagRendering>,
+    fallback: CustomTagRendering,
+}
+
+impl Default for CustomTagRegistry {
+    fn default() -> Self {
+        CustomTagRegistry {
+            renderings: HashMap::new(),
+            fallback: CustomTagRendering::Transparent,
         }
     }
 }
 
+impl CustomTagRegistry {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    pub fn register(&mut self, name: impl Into<String>, rendering: CustomTagRendering) {
+        self.renderings.insert(name.into(), rendering);
+    }
+
+    pub fn set_fallback(&mut self, rendering: CustomTagRendering) {
+        self.fallback = rendering;
+    }
+
+    pub fn rendering(&self, name: &str) -> &CustomTagRendering {
+        self.renderings.get(name).unwrap_or(&self.fallback)
+    }
+}
+
+impl<'a> ast::Document<'a> {
+    pub fn into_tokens(self) -> EventIter<'a, 'a> {
+        self.into_tokens_with_custom_tags(Default::default())
+    }
+
+    pub fn into_tokens_with_custom_tags(self, custom_tags: CustomTagRegistry) -> EventIter<'a, 'a> {
+        EventIter::new(
+            vec![
+                Frame::OutOfBands(Nodes::Owned(self.outofbands.into_iter())),
+                Frame::Blocks(Nodes::Owned(self.blocks.into_iter())),
+            ],
+            custom_tags,
+        )
+    }
+
+    /// Like `into_tokens`, but borrows the document so that it can be rendered again.
+    pub fn tokens(&self) -> EventIter<'_, 'a> {
+        self.tokens_with_custom_tags(Default::default())
+    }
+
+    pub fn tokens_with_custom_tags(&self, custom_tags: CustomTagRegistry) -> EventIter<'_, 'a> {
+        EventIter::new(
+            vec![
+                Frame::OutOfBands(Nodes::Borrowed(self.outofbands.iter())),
+                Frame::Blocks(Nodes::Borrowed(self.blocks.iter())),
+            ],
+            custom_tags,
+        )
+    }
+}
+
 impl<'a> clause::DocumentClauseList<'a> {
-    pub fn into_tokens(self) -> EventIter<'a> {
-        let mut iter = EventIter {
-            items: VecDeque::new(),
-        };
-        for (idx, clause) in self.clauses.into_iter().enumerate() {
-            if idx != 0 {
-                iter.items
-                    .push_back(EventIterItem::Event(pulldown_cmark::Event::Rule))
-            }
-            iter.items
-                .push_back(EventIterItem::Event(pulldown_cmark::Event::Start(
-                    pulldown_cmark::Tag::List(Some(clause.clause_idx as _)),
-                )));
-            iter.items
-                .push_back(EventIterItem::Event(pulldown_cmark::Event::Start(
-                    pulldown_cmark::Tag::Item,
-                )));
-            iter.items.extend(
-                clause
-                    .clause_contents
-                    .into_iter()
-                    .map(EventIterItem::Inline),
-            );
-            iter.items
-                .push_back(EventIterItem::Event(pulldown_cmark::Event::End(
-                    pulldown_cmark::Tag::Item,
-                )));
-            iter.items
-                .push_back(EventIterItem::Event(pulldown_cmark::Event::End(
-                    pulldown_cmark::Tag::List(Some(clause.clause_idx as _)),
-                )));
-            for (lang, lang_items) in clause.clause_translations {
-                iter.items
-                    .push_back(EventIterItem::Event(pulldown_cmark::Event::Start(
-                        pulldown_cmark::Tag::Heading(3),
-                    )));
-                iter.items
-                    .push_back(EventIterItem::Event(pulldown_cmark::Event::Text(lang)));
-                iter.items
-                    .push_back(EventIterItem::Event(pulldown_cmark::Event::End(
-                        pulldown_cmark::Tag::Heading(3),
-                    )));
-                iter.items
-                    .push_back(EventIterItem::Event(pulldown_cmark::Event::Start(
-                        pulldown_cmark::Tag::Paragraph,
-                    )));
-                iter.items
-                    .extend(lang_items.into_iter().map(|x| EventIterItem::Inline(x)));
-                iter.items
-                    .push_back(EventIterItem::Event(pulldown_cmark::Event::End(
-                        pulldown_cmark::Tag::Paragraph,
-                    )));
-            }
+    pub fn into_tokens(self) -> EventIter<'a, 'a> {
+        EventIter::new(
+            vec![Frame::Clauses {
+                clauses: self.clauses.into_iter(),
+                source_name: self.source_name,
+                is_first: true,
+            }],
+            Default::default(),
+        )
+    }
+}
+
+/// The events of a clause in the clause file, in order, each list of inline nodes being
+/// walked in place.
+fn clause_frames<'d, 'a>(
+    mut clause: clause::Clause<'a>,
+    source_name: Option<&str>,
+    is_first: bool,
+) -> Vec<Frame<'d, 'a>> {
+    use pulldown_cmark::{Event, Tag};
+
+    let mut translations = Vec::new();
+    if let Some(language) = clause.implicit_source_language() {
+        translations.push((language.to_string().into(), clause.clause_contents.clone()));
+    }
+    translations.extend(
+        mem::take(&mut clause.clause_translations)
+            .into_iter()
+            .map(|(lang, contents)| (lang, clause::ClauseContents::Owned(contents))),
+    );
+
+    let mut events = Vec::new();
+    if !is_first {
+        events.push(Event::Rule);
+    }
+    if clause.clause_span.is_known() {
+        let location = clause.location(source_name);
+        events.push(Event::Html(format!("<!-- {} -->\n", location).into()));
+    }
+    events.push(Event::Html(clause.context_comment().into()));
+    for annotation in clause.clause_annotations {
+        events.push(Event::Html(
+            format!("<!-- {} -->\n", annotation.replace("--", "- -")).into(),
+        ));
+    }
+    for note in clause.clause_notes {
+        events.extend(vec![
+            Event::Start(Tag::BlockQuote),
+            Event::Start(Tag::Paragraph),
+            Event::Text(note.into()),
+            Event::End(Tag::Paragraph),
+            Event::End(Tag::BlockQuote),
+        ]);
+    }
+    let list = Tag::List(Some(clause.clause_idx as _));
+    events.push(Event::Start(list.clone()));
+    events.push(Event::Start(Tag::Item));
+
+    let mut frames = vec![
+        Frame::Events(events.into_iter()),
+        Frame::Inlines(Nodes::from_contents(clause.clause_contents)),
+        Frame::Events(vec![Event::End(Tag::Item), Event::End(list)].into_iter()),
+    ];
+    for (lang, lang_items) in translations {
+        let mut heading = lang.to_string();
+        if clause.clause_fuzzy_languages.contains(&lang) {
+            heading = format!("{} {}", heading, clause::FUZZY_MARKER);
+        }
+        if clause.clause_machine_languages.contains(&lang) {
+            heading = format!("{} {}", heading, clause::MACHINE_TRANSLATED_MARKER);
         }
-        iter
+        frames.push(Frame::Events(
+            vec![
+                Event::Start(Tag::Heading(3)),
+                Event::Text(heading.into()),
+                Event::End(Tag::Heading(3)),
+                Event::Start(Tag::Paragraph),
+            ]
+            .into_iter(),
+        ));
+        frames.push(Frame::Inlines(Nodes::from_contents(lang_items)));
+        frames.push(Frame::Event(Event::End(Tag::Paragraph)));
     }
+    frames
 }
 
-pub struct EventIter<'a> {
-    items: VecDeque<EventIterItem<'a>>,
+/// The nodes of a list, either moved out of an owned document or borrowed from one.
+enum Nodes<'d, T> {
+    Owned(vec::IntoIter<T>),
+    Borrowed(core::slice::Iter<'d, T>),
 }
 
-pub trait ExtendFront<A> {
-    fn extend_front<T: DoubleEndedIterator<Item = A>>(&mut self, iter: T);
+enum Node<'d, T> {
+    Owned(T),
+    Borrowed(&'d T),
 }
 
-impl<A> ExtendFront<A> for VecDeque<A> {
-    fn extend_front<T: DoubleEndedIterator<Item = A>>(&mut self, mut iter: T) {
-        while let Some(v) = iter.next_back() {
-            self.push_front(v)
+impl<'d, 'a> Nodes<'d, ast::InlineNode<'a>> {
+    fn from_contents(contents: clause::ClauseContents<'a>) -> Self
+    where
+        'a: 'd,
+    {
+        match contents {
+            clause::ClauseContents::Borrowed(contents) => Nodes::Borrowed(contents.iter()),
+            clause::ClauseContents::Owned(contents) => Nodes::Owned(contents.into_iter()),
+        }
+    }
+}
+
+impl<'d, T> Iterator for Nodes<'d, T> {
+    type Item = Node<'d, T>;
+    fn next(&mut self) -> Option<Self::Item> {
+        match self {
+            Nodes::Owned(iter) => iter.next().map(Node::Owned),
+            Nodes::Borrowed(iter) => iter.next().map(Node::Borrowed),
+        }
+    }
+}
+
+/// What is left to emit at one level of the tree.
+enum Frame<'d, 'a> {
+    Event(pulldown_cmark::Event<'a>),
+    Events(vec::IntoIter<pulldown_cmark::Event<'a>>),
+    Blocks(Nodes<'d, ast::BlockNode<'a>>),
+    OutOfBands(Nodes<'d, ast::OutOfBandNode<'a>>),
+    Inlines(Nodes<'d, ast::InlineNode<'a>>),
+    Clauses {
+        clauses: vec::IntoIter<clause::Clause<'a>>,
+        source_name: Option<pulldown_cmark::CowStr<'a>>,
+        is_first: bool,
+    },
+}
+
+/// Emits the events of a document lazily, walking it with a stack of the node lists
+/// being visited.
+///
+/// The document is either owned by the iterator or borrowed for `'d`, in which case only
+/// the tags are cloned into the events.
+pub struct EventIter<'d, 'a> {
+    stack: Vec<Frame<'d, 'a>>,
+    custom_tags: CustomTagRegistry,
+    custom_tag_counts: HashMap<String, usize>,
+}
+
+impl<'d, 'a> EventIter<'d, 'a> {
+    fn new(stack: Vec<Frame<'d, 'a>>, custom_tags: CustomTagRegistry) -> Self {
+        EventIter {
+            stack,
+            custom_tags,
+            custom_tag_counts: HashMap::new(),
+        }
+    }
+
+    pub(crate) fn from_inlines(inlines: ast::InlineNodeList<'a>) -> Self {
+        EventIter::new(
+            vec![Frame::Inlines(Nodes::Owned(inlines.into_iter()))],
+            Default::default(),
+        )
+    }
+
+    /// Returns the first event of a node, leaving its children and end on the stack.
+    fn open(
+        &mut self,
+        tag: ConvertedTag<'a>,
+        children: Option<Frame<'d, 'a>>,
+        is_block: bool,
+    ) -> Option<pulldown_cmark::Event<'a>> {
+        match unconvert_tag(tag) {
+            UnconvertedTag::SpanEvent(start, end)
+            | UnconvertedTag::SpanEventWithoutLength(start, end) => {
+                self.stack.push(Frame::Event(end));
+                self.stack.extend(children);
+                Some(start)
+            }
+            UnconvertedTag::SingleEvent(event) => Some(event),
+            UnconvertedTag::TransparentEvent => {
+                self.stack.extend(children);
+                None
+            }
+            UnconvertedTag::Custom(name) => self.open_custom_tag(&name, children, is_block),
         }
     }
+
+    fn open_custom_tag(
+        &mut self,
+        name: &str,
+        children: Option<Frame<'d, 'a>>,
+        is_block: bool,
+    ) -> Option<pulldown_cmark::Event<'a>> {
+        let (start, end) = match self.custom_tags.rendering(name) {
+            CustomTagRendering::Transparent => {
+                self.stack.extend(children);
+                return None;
+            }
+            CustomTagRendering::Omit => return None,
+            CustomTagRendering::RawHtml { start, end } => (start.clone(), end.clone()),
+            CustomTagRendering::HtmlComment => {
+                (format!("<!-- {} -->", name), format!("<!-- /{} -->", name))
+            }
+            CustomTagRendering::IdentifiedElement { attribute, ids } => {
+                let count = self.custom_tag_counts.entry(name.to_string()).or_insert(0);
+                *count += 1;
+                let id = ids
+                    .get(*count - 1)
+                    .cloned()
+                    .unwrap_or_else(|| count.to_string());
+                let element = if is_block { "div" } else { "span" };
+                (
+                    format!("<{} {}=\"{}\">", element, attribute, id),
+                    format!("</{}>", element),
+                )
+            }
+        };
+        let (start, end) = if is_block {
+            (start + "\n", end + "\n")
+        } else {
+            (start, end)
+        };
+        self.stack
+            .push(Frame::Event(pulldown_cmark::Event::Html(end.into())));
+        self.stack.extend(children);
+        Some(pulldown_cmark::Event::Html(start.into()))
+    }
+
+    fn open_block(
+        &mut self,
+        block: Node<'d, ast::BlockNode<'a>>,
+    ) -> Option<pulldown_cmark::Event<'a>> {
+        use ast::BlockNode;
+
+        let (tag, children) = match block {
+            Node::Owned(BlockNode::Container { tag, children, .. }) => (
+                ConvertedInbandTag::ContainerBlock(tag),
+                Frame::Blocks(Nodes::Owned(children.into_iter())),
+            ),
+            Node::Borrowed(BlockNode::Container { tag, children, .. }) => (
+                ConvertedInbandTag::ContainerBlock(tag.clone()),
+                Frame::Blocks(Nodes::Borrowed(children.iter())),
+            ),
+            Node::Owned(BlockNode::Leaf { tag, contents, .. }) => (
+                ConvertedInbandTag::LeafBlock(tag),
+                Frame::Inlines(Nodes::Owned(contents.into_iter())),
+            ),
+            Node::Borrowed(BlockNode::Leaf { tag, contents, .. }) => (
+                ConvertedInbandTag::LeafBlock(tag.clone()),
+                Frame::Inlines(Nodes::Borrowed(contents.iter())),
+            ),
+            Node::Owned(BlockNode::Markup { tag, .. }) => {
+                let tag = ConvertedTag::InBand(ConvertedInbandTag::MarkupBlock(tag));
+                return self.open(tag, None, true);
+            }
+            Node::Borrowed(BlockNode::Markup { tag, .. }) => {
+                let tag = ConvertedTag::InBand(ConvertedInbandTag::MarkupBlock(tag.clone()));
+                return self.open(tag, None, true);
+            }
+        };
+        self.open(ConvertedTag::InBand(tag), Some(children), true)
+    }
+
+    fn open_outofband(
+        &mut self,
+        outofband: Node<'d, ast::OutOfBandNode<'a>>,
+    ) -> Option<pulldown_cmark::Event<'a>> {
+        use ast::OutOfBandNode;
+
+        let (tag, children) = match outofband {
+            Node::Owned(OutOfBandNode::OutOfBandContainer { tag, children, .. }) => (
+                ConvertedOutofbandTag::OutOfBandContainer(tag),
+                Frame::Blocks(Nodes::Owned(children.into_iter())),
+            ),
+            Node::Borrowed(OutOfBandNode::OutOfBandContainer { tag, children, .. }) => (
+                ConvertedOutofbandTag::OutOfBandContainer(tag.clone()),
+                Frame::Blocks(Nodes::Borrowed(children.iter())),
+            ),
+            Node::Owned(OutOfBandNode::OutOfBandLeaf { tag, contents, .. }) => (
+                ConvertedOutofbandTag::OutOfBandLeaf(tag),
+                Frame::Inlines(Nodes::Owned(contents.into_iter())),
+            ),
+            Node::Borrowed(OutOfBandNode::OutOfBandLeaf { tag, contents, .. }) => (
+                ConvertedOutofbandTag::OutOfBandLeaf(tag.clone()),
+                Frame::Inlines(Nodes::Borrowed(contents.iter())),
+            ),
+        };
+        self.open(ConvertedTag::OutOfBand(tag), Some(children), true)
+    }
+
+    fn open_inline(
+        &mut self,
+        inline: Node<'d, ast::InlineNode<'a>>,
+    ) -> Option<pulldown_cmark::Event<'a>> {
+        use ast::InlineNode;
+
+        let (tag, children) = match inline {
+            Node::Owned(InlineNode::Surrounding { tag, contents, .. }) => (
+                ConvertedInbandTag::SurroundingInline(tag),
+                Some(Frame::Inlines(Nodes::Owned(contents.into_iter()))),
+            ),
+            Node::Borrowed(InlineNode::Surrounding { tag, contents, .. }) => (
+                ConvertedInbandTag::SurroundingInline(tag.clone()),
+                Some(Frame::Inlines(Nodes::Borrowed(contents.iter()))),
+            ),
+            Node::Owned(InlineNode::Content { tag, .. }) => {
+                (ConvertedInbandTag::ContentInline(tag), None)
+            }
+            Node::Borrowed(InlineNode::Content { tag, .. }) => {
+                (ConvertedInbandTag::ContentInline(tag.clone()), None)
+            }
+        };
+        self.open(ConvertedTag::InBand(tag), children, false)
+    }
 }
 
-impl<'a> Iterator for EventIter<'a> {
+impl<'d, 'a> Iterator for EventIter<'d, 'a> {
     type Item = pulldown_cmark::Event<'a>;
     fn next(&mut self) -> Option<Self::Item> {
-        'restart: loop {
-            let next_item = self.items.pop_front()?;
-            match next_item {
-                EventIterItem::Event(e) => return Some(e),
-                EventIterItem::Inline(i) => match i {
-                    ast::InlineNode::Surrounding { tag, contents } => {
-                        match unconvert_tag(ConvertedTag::InBand(
-                            ConvertedInbandTag::SurroundingInline(tag),
-                        )) {
-                            UnconvertedTag::SpanEvent(start, end) => {
-                                self.items.push_front(EventIterItem::Event(end));
-                                self.items
-                                    .extend_front(contents.into_iter().map(EventIterItem::Inline));
-                                return Some(start);
-                            }
-                            UnconvertedTag::TransparentEvent => {
-                                self.items
-                                    .extend_front(contents.into_iter().map(EventIterItem::Inline));
-                                continue 'restart;
-                            }
-                            UnconvertedTag::SpanEventWithoutLength(_, _)
-                            | UnconvertedTag::SingleEvent(_) => {
-                                unreachable!();
-                            }
-                            UnconvertedTag::Custom(_) => {
-                                //FIXME: todo!();
-                                self.items
-                                    .extend_front(contents.into_iter().map(EventIterItem::Inline));
-                                continue 'restart;
-                            }
-                        };
-                    }
-                    ast::InlineNode::Content { tag } => {
-                        match unconvert_tag(ConvertedTag::InBand(
-                            ConvertedInbandTag::ContentInline(tag),
-                        )) {
-                            UnconvertedTag::SpanEvent(_, _) | UnconvertedTag::TransparentEvent => {
-                                unreachable!()
-                            }
-                            UnconvertedTag::SpanEventWithoutLength(start, end) => {
-                                self.items.push_front(EventIterItem::Event(end));
-                                return Some(start);
-                            }
-                            UnconvertedTag::SingleEvent(e) => {
-                                return Some(e);
-                            }
-                            UnconvertedTag::Custom(_) => {
-                                todo!();
-                            }
-                        }
+        loop {
+            let event = match self.stack.last_mut()? {
+                Frame::Event(_) => match self.stack.pop() {
+                    Some(Frame::Event(event)) => Some(event),
+                    _ => unreachable!(),
+                },
+                Frame::Events(events) => match events.next() {
+                    Some(event) => Some(event),
+                    None => {
+                        self.stack.pop();
+                        None
                     }
                 },
-                EventIterItem::Block(b) => match b {
-                    ast::BlockNode::Container { tag, children } => {
-                        match unconvert_tag(ConvertedTag::InBand(
-                            ConvertedInbandTag::ContainerBlock(tag),
-                        )) {
-                            UnconvertedTag::SpanEvent(start, end) => {
-                                self.items.push_front(EventIterItem::Event(end));
-                                self.items
-                                    .extend_front(children.into_iter().map(EventIterItem::Block));
-                                return Some(start);
-                            }
-                            UnconvertedTag::TransparentEvent => {
-                                self.items
-                                    .extend_front(children.into_iter().map(EventIterItem::Block));
-                                continue 'restart;
-                            }
-                            UnconvertedTag::SpanEventWithoutLength(_, _)
-                            | UnconvertedTag::SingleEvent(_) => unreachable!(),
-                            UnconvertedTag::Custom(_) => todo!(),
-                        }
+                Frame::Blocks(nodes) => match nodes.next() {
+                    Some(block) => self.open_block(block),
+                    None => {
+                        self.stack.pop();
+                        None
                     }
-                    ast::BlockNode::Leaf { tag, contents } => {
-                        match unconvert_tag(ConvertedTag::InBand(ConvertedInbandTag::LeafBlock(
-                            tag,
-                        ))) {
-                            UnconvertedTag::SpanEvent(start, end) => {
-                                self.items.push_front(EventIterItem::Event(end));
-                                self.items
-                                    .extend_front(contents.into_iter().map(EventIterItem::Inline));
-                                return Some(start);
-                            }
-                            UnconvertedTag::TransparentEvent => {
-                                self.items
-                                    .extend_front(contents.into_iter().map(EventIterItem::Inline));
-                                continue 'restart;
-                            }
-                            UnconvertedTag::SpanEventWithoutLength(_, _)
-                            | UnconvertedTag::SingleEvent(_) => unreachable!(),
-                            UnconvertedTag::Custom(_) => todo!(),
-                        }
+                },
+                Frame::OutOfBands(nodes) => match nodes.next() {
+                    Some(outofband) => self.open_outofband(outofband),
+                    None => {
+                        self.stack.pop();
+                        None
                     }
-                    ast::BlockNode::Markup { tag } => {
-                        match unconvert_tag(ConvertedTag::InBand(ConvertedInbandTag::MarkupBlock(
-                            tag,
-                        ))) {
-                            UnconvertedTag::SpanEvent(_, _) | UnconvertedTag::TransparentEvent => {
-                                unreachable!()
-                            }
-                            UnconvertedTag::SpanEventWithoutLength(start, end) => {
-                                self.items.push_front(EventIterItem::Event(end));
-                                return Some(start);
-                            }
-                            UnconvertedTag::SingleEvent(e) => {
-                                return Some(e);
-                            }
-                            UnconvertedTag::Custom(_) => {
-                                todo!();
-                            }
-                        }
+                },
+                Frame::Inlines(nodes) => match nodes.next() {
+                    Some(inline) => self.open_inline(inline),
+                    None => {
+                        self.stack.pop();
+                        None
                     }
                 },
-                EventIterItem::OutOfBand(o) => match o {
-                    ast::OutOfBandNode::OutOfBandContainer { tag, children } => {
-                        match unconvert_tag(ConvertedTag::OutOfBand(
-                            ConvertedOutofbandTag::OutOfBandContainer(tag),
-                        )) {
-                            UnconvertedTag::SpanEvent(start, end) => {
-                                self.items.push_front(EventIterItem::Event(end));
-                                self.items
-                                    .extend_front(children.into_iter().map(EventIterItem::Block));
-                                return Some(start);
-                            }
-                            UnconvertedTag::TransparentEvent => {
-                                self.items
-                                    .extend_front(children.into_iter().map(EventIterItem::Block));
-                                continue 'restart;
-                            }
-                            UnconvertedTag::SpanEventWithoutLength(_, _)
-                            | UnconvertedTag::SingleEvent(_) => unreachable!(),
-                            UnconvertedTag::Custom(_) => todo!(),
-                        }
+                Frame::Clauses {
+                    clauses,
+                    source_name,
+                    is_first,
+                } => match clauses.next() {
+                    Some(clause) => {
+                        let frames = clause_frames(clause, source_name.as_deref(), *is_first);
+                        *is_first = false;
+                        self.stack.extend(frames.into_iter().rev());
+                        None
                     }
-                    ast::OutOfBandNode::OutOfBandLeaf { tag, contents } => {
-                        match unconvert_tag(ConvertedTag::OutOfBand(
-                            ConvertedOutofbandTag::OutOfBandLeaf(tag),
-                        )) {
-                            UnconvertedTag::SpanEvent(start, end) => {
-                                self.items.push_front(EventIterItem::Event(end));
-                                self.items
-                                    .extend_front(contents.into_iter().map(EventIterItem::Inline));
-                                return Some(start);
-                            }
-                            UnconvertedTag::TransparentEvent => {
-                                self.items
-                                    .extend_front(contents.into_iter().map(EventIterItem::Inline));
-                                continue 'restart;
-                            }
-                            UnconvertedTag::SpanEventWithoutLength(_, _)
-                            | UnconvertedTag::SingleEvent(_) => unreachable!(),
-                            UnconvertedTag::Custom(_) => todo!(),
-                        }
+                    None => {
+                        self.stack.pop();
+                        None
                     }
                 },
+            };
+            if event.is_some() {
+                return event;
             }
         }
     }
 }
 
-#[derive(Clone)]
-enum EventIterItem<'a> {
-    Event(pulldown_cmark::Event<'a>),
-    Inline(ast::InlineNode<'a>),
-    Block(ast::BlockNode<'a>),
-    OutOfBand(ast::OutOfBandNode<'a>),
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{parse, segmented};
+    use pulldown_cmark::{Event, Tag};
+
+    /// `Hello.` with its paragraph wrapped in a `Box` custom block, or its text in a
+    /// `Note` custom inline.
+    fn custom_doc(is_block: bool) -> ast::Document<'static> {
+        let mut doc = parse("Hello.\n");
+        if is_block {
+            let paragraph = doc.blocks.remove(0);
+            doc.blocks.push(ast::BlockNode::Container {
+                tag: ast::ContainerBlockTag::Custom("Box".into()),
+                children: vec![paragraph],
+                span: Default::default(),
+            });
+        } else if let ast::BlockNode::Leaf { contents, .. } = &mut doc.blocks[0] {
+            let text = core::mem::take(contents);
+            contents.push(ast::InlineNode::Surrounding {
+                tag: ast::SurroundingInlineTag::Custom("Note".into()),
+                contents: text,
+                span: Default::default(),
+            });
+        }
+        doc
+    }
+
+    fn render(is_block: bool, custom_tags: &CustomTagRegistry) -> Vec<Event<'static>> {
+        custom_doc(is_block)
+            .into_tokens_with_custom_tags(custom_tags.clone())
+            .collect()
+    }
+
+    fn paragraph(inner: Vec<Event<'static>>) -> Vec<Event<'static>> {
+        let mut events = vec![Event::Start(Tag::Paragraph)];
+        events.extend(inner);
+        events.push(Event::End(Tag::Paragraph));
+        events
+    }
+
+    fn registry(rendering: CustomTagRendering) -> CustomTagRegistry {
+        let mut custom_tags = CustomTagRegistry::new();
+        custom_tags.register("Box", rendering.clone());
+        custom_tags.register("Note", rendering);
+        custom_tags
+    }
+
+    #[test]
+    fn transparent_tags_emit_their_children() {
+        let custom_tags = registry(CustomTagRendering::Transparent);
+        let expected = paragraph(vec![Event::Text("Hello.".into())]);
+        assert_eq!(render(false, &custom_tags), expected);
+        assert_eq!(render(true, &custom_tags), expected);
+    }
+
+    #[test]
+    fn raw_html_tags_surround_their_children() {
+        let custom_tags = registry(CustomTagRendering::RawHtml {
+            start: "<b>".into(),
+            end: "</b>".into(),
+        });
+        assert_eq!(
+            render(false, &custom_tags),
+            paragraph(vec![
+                Event::Html("<b>".into()),
+                Event::Text("Hello.".into()),
+                Event::Html("</b>".into()),
+            ])
+        );
+        let mut expected = vec![Event::Html("<b>\n".into())];
+        expected.extend(paragraph(vec![Event::Text("Hello.".into())]));
+        expected.push(Event::Html("</b>\n".into()));
+        assert_eq!(render(true, &custom_tags), expected);
+    }
+
+    #[test]
+    fn comment_tags_surround_their_children() {
+        let custom_tags = registry(CustomTagRendering::HtmlComment);
+        assert_eq!(
+            render(false, &custom_tags),
+            paragraph(vec![
+                Event::Html("<!-- Note -->".into()),
+                Event::Text("Hello.".into()),
+                Event::Html("<!-- /Note -->".into()),
+            ])
+        );
+        let mut expected = vec![Event::Html("<!-- Box -->\n".into())];
+        expected.extend(paragraph(vec![Event::Text("Hello.".into())]));
+        expected.push(Event::Html("<!-- /Box -->\n".into()));
+        assert_eq!(render(true, &custom_tags), expected);
+    }
+
+    #[test]
+    fn omitted_tags_emit_nothing() {
+        let custom_tags = registry(CustomTagRendering::Omit);
+        assert_eq!(render(false, &custom_tags), paragraph(vec![]));
+        assert_eq!(render(true, &custom_tags), vec![]);
+    }
+
+    #[test]
+    fn unregistered_tags_use_the_fallback() {
+        let mut custom_tags = CustomTagRegistry::new();
+        let expected = paragraph(vec![Event::Text("Hello.".into())]);
+        assert_eq!(render(false, &custom_tags), expected);
+        assert_eq!(render(true, &custom_tags), expected);
+
+        custom_tags.set_fallback(CustomTagRendering::Omit);
+        custom_tags.register("Note", CustomTagRendering::Transparent);
+        assert_eq!(render(false, &custom_tags), expected);
+        assert_eq!(render(true, &custom_tags), vec![]);
+    }
+
+    #[test]
+    fn borrowed_and_owned_documents_emit_the_same_events() {
+        let text = "Intro.[^note] Two.\n\n\
+                    | Head. | Other. |\n|---|:-:|\n| *Cell.* | `code` |\n\n\
+                    - Outer.\n  1. Inner. Still inner.\n     > Quoted.\n\n\
+                    [^note]: A *footnote*. With two sentences.\n";
+        let mut doc = ast::Document::from_markdown(text, pulldown_cmark::Options::all()).unwrap();
+        doc.perform_sentence_segment();
+        let borrowed: Vec<Event<'_>> = doc.tokens().collect();
+        let owned: Vec<Event<'_>> = doc.clone().into_tokens().collect();
+        assert!(borrowed
+            .iter()
+            .any(|event| matches!(event, Event::Start(Tag::FootnoteDefinition(_)))));
+        assert!(borrowed
+            .iter()
+            .any(|event| matches!(event, Event::Start(Tag::Table(_)))));
+        assert_eq!(borrowed, owned);
+    }
+
+    /// The ids of the sentences of `text` rendered with `IdentifiedElement`, as read
+    /// back from the rendered events, and the ids the clause list gives them.
+    fn rendered_ids(
+        text: &str,
+        attribute: &str,
+        style: clause::SentenceIdStyle,
+    ) -> (Vec<String>, Vec<String>) {
+        let doc = segmented(text);
+        let ids = doc.extract_clause_list(&"en-US".into()).sentence_ids(style);
+        let mut custom_tags = CustomTagRegistry::new();
+        custom_tags.register(
+            crate::sentence_segment::SENTENCE_TAG_NAME,
+            CustomTagRendering::IdentifiedElement {
+                attribute: attribute.to_string(),
+                ids: ids.clone(),
+            },
+        );
+        let prefix = format!("<span {}=\"", attribute);
+        let rendered = doc
+            .into_tokens_with_custom_tags(custom_tags)
+            .filter_map(|event| match event {
+                Event::Html(html) => {
+                    Some(html.strip_prefix(&prefix)?.strip_suffix("\">")?.to_string())
+                }
+                _ => None,
+            })
+            .collect();
+        (rendered, ids)
+    }
+
+    #[test]
+    fn sentences_are_identified_by_clause_index() {
+        let (rendered, ids) = rendered_ids(
+            "Same. Other.\n\nSame.\n",
+            "data-clause-id",
+            clause::SentenceIdStyle::ClauseIndex,
+        );
+        assert_eq!(rendered, ["1", "2", "3"]);
+        assert_eq!(rendered, ids);
+    }
+
+    #[test]
+    fn sentences_are_identified_by_digest() {
+        let (rendered, ids) = rendered_ids(
+            "Same. Other.\n\nSame.\n",
+            "data-mdt-id",
+            clause::SentenceIdStyle::ContentDigest,
+        );
+        assert_eq!(rendered, ids);
+        assert_eq!(ids.len(), 3);
+        assert_ne!(ids[0], ids[1]);
+        assert_eq!(ids[2], format!("{}-2", ids[0]));
+    }
 }
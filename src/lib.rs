@@ -1,9 +1,25 @@
 extern crate alloc;
 
+pub mod apply;
 pub mod ast;
+pub mod batch;
+pub mod bilingual;
 pub mod clause;
 pub mod from_tokens;
+pub mod glossary;
 pub mod into_tokens;
+pub mod mdbook;
+pub mod notes;
+pub mod notranslate;
+pub mod pseudo;
+pub mod qa;
 pub mod sentence_segment;
+#[cfg(feature = "serde")]
+mod serde_support;
+pub mod stats;
 pub mod tag;
+#[cfg(test)]
+mod test_support;
 pub mod textualize;
+pub mod translator;
+pub mod visit;
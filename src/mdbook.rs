@@ -0,0 +1,237 @@
+//! mdBook's preprocessor protocol, applying the translations from clause files to each chapter.
+//!
+//! The preprocessor is configured in `book.toml`:
+//!
+//! ```toml
+//! [preprocessor.mdtranslation]
+//! command = "mdtranslation-mdbook"
+//! before = ["links"]
+//! language = "zh-CN"
+//! translations = "translations"
+//! ```
+//!
+//! It must run `before` the `links` preprocessor: clause files are extracted from the
+//! chapters as written, and expanding `{{#include}}` first would shift the clause numbering.
+//!
+//! The clause file of each chapter is looked up under the `translations` directory
+//! (relative to the book root) at the chapter's own path. Chapters without a clause file
+//! are passed through unchanged.
+use crate::clause;
+use crate::from_tokens;
+use serde_json::Value;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum MdbookError {
+    #[error("io error: {0}")]
+    IO(#[from] io::Error),
+    #[error("format error: {0}")]
+    Fmt(#[from] fmt::Error),
+    #[error("json error: {0}")]
+    Json(#[from] serde_json::Error),
+    #[error("{0}: from tokens error: {1}")]
+    FromTokens(PathBuf, Box<from_tokens::FromTokensError>),
+    #[error("{0}: clause file error: {1}")]
+    ClauseFile(PathBuf, clause::ClauseFileError),
+    #[error("malformed preprocessor input: {0}")]
+    Protocol(&'static str),
+}
+
+/// The settings under `[preprocessor.mdtranslation]`.
+#[derive(Clone, Debug)]
+pub struct Config {
+    pub language: String,
+    pub translations_dir: PathBuf,
+}
+
+impl Config {
+    /// Reads the settings from the preprocessor context, with `language` taking
+    /// precedence over the configured one. Returns `None` without any language.
+    pub fn from_context(
+        context: &Value,
+        language: Option<String>,
+    ) -> Result<Option<Config>, MdbookError> {
+        let root = context
+            .get("root")
+            .and_then(Value::as_str)
+            .ok_or(MdbookError::Protocol("missing book root"))?;
+        let preprocessor_config = context.pointer("/config/preprocessor/mdtranslation");
+        let language = match language.or_else(|| {
+            preprocessor_config
+                .and_then(|config| config.get("language"))
+                .and_then(Value::as_str)
+                .map(str::to_string)
+        }) {
+            Some(language) => language,
+            None => return Ok(None),
+        };
+        let translations_dir = preprocessor_config
+            .and_then(|config| config.get("translations"))
+            .and_then(Value::as_str)
+            .unwrap_or("translations");
+        Ok(Some(Config {
+            language,
+            translations_dir: Path::new(root).join(translations_dir),
+        }))
+    }
+}
+
+/// Parses and segments `content`, applies the translations from the clause file at
+/// `clause_path` and renders the result. `chapter_path` only names the chapter in errors.
+pub fn translate_chapter(
+    content: &str,
+    chapter_path: &Path,
+    clause_path: &Path,
+    language: &str,
+) -> Result<String, MdbookError> {
+    let clauses_text = match fs::read_to_string(clause_path) {
+        Ok(text) => text,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(content.to_string()),
+        Err(e) => return Err(e.into()),
+    };
+    let clauses_reader = pulldown_cmark::Parser::new(&clauses_text);
+    let clauses_ast = from_tokens::cmark_ast_from_tokens(clauses_reader, &clauses_text)
+        .map_err(|e| MdbookError::FromTokens(clause_path.to_owned(), Box::new(e)))?;
+    let clause_list = clause::DocumentClauseList::from_clause_document(clauses_ast)
+        .map_err(|e| MdbookError::ClauseFile(clause_path.to_owned(), e))?;
+
+    let reader = pulldown_cmark::Parser::new(content);
+    let mut ast = from_tokens::cmark_ast_from_tokens(reader, content)
+        .map_err(|e| MdbookError::FromTokens(chapter_path.to_owned(), Box::new(e)))?;
+    ast.perform_sentence_segment();
+    ast.apply_translations(&clause_list, language);
+    let mut output_text = String::new();
+    let _ = pulldown_cmark_to_cmark::cmark(ast.into_tokens(), &mut output_text, None)?;
+    Ok(output_text)
+}
+
+fn translate_sections(sections: &mut Value, config: &Config) -> Result<(), MdbookError> {
+    let sections = match sections.as_array_mut() {
+        Some(sections) => sections,
+        None => return Ok(()),
+    };
+    for section in sections {
+        let chapter = match section.get_mut("Chapter") {
+            Some(chapter) => chapter,
+            None => continue,
+        };
+        let path = chapter
+            .get("path")
+            .and_then(Value::as_str)
+            .map(PathBuf::from);
+        if let (Some(path), Some(Value::String(content))) = (path, chapter.get_mut("content")) {
+            let clause_path = config.translations_dir.join(&path);
+            *content = translate_chapter(content, &path, &clause_path, &config.language)?;
+        }
+        if let Some(sub_items) = chapter.get_mut("sub_items") {
+            translate_sections(sub_items, config)?;
+        }
+    }
+    Ok(())
+}
+
+/// Handles one preprocessor run: takes the `[context, book]` JSON mdBook writes to the
+/// preprocessor and returns the book JSON with its chapters translated.
+pub fn preprocess(input_text: &str, language: Option<String>) -> Result<String, MdbookError> {
+    let input: Value = serde_json::from_str(input_text)?;
+    let (context, mut book) = match input {
+        Value::Array(mut items) if items.len() == 2 => {
+            let book = items.pop().unwrap();
+            (items.pop().unwrap(), book)
+        }
+        _ => return Err(MdbookError::Protocol("expected [context, book]")),
+    };
+    if let Some(config) = Config::from_context(&context, language)? {
+        let sections = book
+            .get_mut("sections")
+            .ok_or(MdbookError::Protocol("missing book sections"))?;
+        translate_sections(sections, &config)?;
+    }
+    Ok(serde_json::to_string(&book)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::temp_dir;
+
+    const CLAUSES: &str =
+        "<!-- a.md:1:1 -->\n\n1. One.\n\n### en-US\n\nOne.\n\n### zh-CN\n\n一。\n\n---\n";
+
+    fn payload(root: &Path, content: &str) -> String {
+        serde_json::json!([
+            {
+                "root": root,
+                "config": {
+                    "preprocessor": {"mdtranslation": {"language": "zh-CN"}}
+                },
+                "renderer": "html",
+                "mdbook_version": "0.4.0"
+            },
+            {
+                "sections": [
+                    {"Chapter": {
+                        "name": "A",
+                        "content": content,
+                        "path": "a.md",
+                        "sub_items": [
+                            {"Chapter": {
+                                "name": "B",
+                                "content": content,
+                                "path": "b.md",
+                                "sub_items": []
+                            }}
+                        ]
+                    }},
+                    "Separator"
+                ],
+                "__non_exhaustive": null
+            }
+        ])
+        .to_string()
+    }
+
+    fn chapters(book: &Value) -> Vec<&str> {
+        let a = &book["sections"][0]["Chapter"];
+        let b = &a["sub_items"][0]["Chapter"];
+        vec![
+            a["content"].as_str().unwrap(),
+            b["content"].as_str().unwrap(),
+        ]
+    }
+
+    #[test]
+    fn chapters_with_clause_files_are_translated() {
+        let root = temp_dir("mdbook");
+        fs::create_dir_all(root.join("translations")).unwrap();
+        fs::write(root.join("translations/a.md"), CLAUSES).unwrap();
+
+        let output = preprocess(&payload(&root, "One. See {{#include x.rs}}\n"), None).unwrap();
+        let book: Value = serde_json::from_str(&output).unwrap();
+        assert_eq!(
+            chapters(&book),
+            ["一。 See {{#include x.rs}}", "One. See {{#include x.rs}}\n"]
+        );
+        assert_eq!(book["sections"][1], "Separator");
+
+        let output = preprocess(&payload(&root, "One.\n"), Some("fr-FR".into())).unwrap();
+        let book: Value = serde_json::from_str(&output).unwrap();
+        assert_eq!(chapters(&book), ["One.", "One.\n"]);
+    }
+
+    #[test]
+    fn malformed_payloads_are_errors() {
+        assert!(matches!(
+            preprocess("{}", None),
+            Err(MdbookError::Protocol(_))
+        ));
+        assert!(matches!(
+            preprocess("[{}, {}]", None),
+            Err(MdbookError::Protocol(_))
+        ));
+    }
+}
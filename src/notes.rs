@@ -0,0 +1,181 @@
+//! Notes for translators, left by authors in HTML comments.
+//!
+//! A comment block such as `<!-- translator: "crate" here means a Rust package -->`
+//! attaches its text to every clause of the block right after it.
+use crate::ast;
+use crate::clause;
+use crate::visit::{walk_outofband, Visitor};
+use core::mem;
+use std::collections::HashMap;
+
+/// The prefix marking the comments that are notes, unless configured otherwise.
+pub const DEFAULT_NOTE_PREFIX: &str = "translator:";
+
+/// Returns the text of each comment in `block`, if it is an HTML block holding nothing
+/// but comments.
+fn html_comments(block: &ast::BlockNode<'_>) -> Option<Vec<String>> {
+    let contents = match block {
+        ast::BlockNode::Leaf {
+            tag: ast::LeafBlockTag::Html,
+            contents,
+            ..
+        } => contents,
+        _ => return None,
+    };
+    let mut text = String::new();
+    for node in contents {
+        match node {
+            ast::InlineNode::Content {
+                tag: ast::ContentInlineTag::RawHtml(s),
+                ..
+            } => text += s,
+            ast::InlineNode::Surrounding { .. } => return None,
+            _ => {}
+        }
+    }
+    let mut comments = Vec::new();
+    let mut rest = text.trim();
+    while !rest.is_empty() {
+        let comment = rest.strip_prefix("<!--")?;
+        let end = comment.find("-->")?;
+        comments.push(comment[..end].trim().to_string());
+        rest = comment[end + 3..].trim_start();
+    }
+    if comments.is_empty() {
+        None
+    } else {
+        Some(comments)
+    }
+}
+
+/// Whether `block` is an HTML block holding comments only, which has nothing to
+/// translate.
+pub(crate) fn is_comment_block(block: &ast::BlockNode<'_>) -> bool {
+    html_comments(block).is_some()
+}
+
+fn block_notes(block: &ast::BlockNode<'_>, note_prefix: &str) -> Vec<String> {
+    html_comments(block)
+        .unwrap_or_default()
+        .iter()
+        .filter_map(|comment| comment.strip_prefix(note_prefix))
+        .map(str::trim)
+        .filter(|note| !note.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+type NoteMap = HashMap<usize, Vec<String>>;
+
+/// Collects the notes of each sentence from the comment blocks before it and around it,
+/// numbering sentences the way clauses are extracted.
+struct NoteCollector<'p> {
+    note_prefix: &'p str,
+    /// The notes of the enclosing blocks.
+    inherited_notes: Vec<String>,
+    idx: usize,
+    notes: NoteMap,
+}
+
+impl<'d, 'a> Visitor<'d, 'a> for NoteCollector<'_> {
+    fn visit_blocks(&mut self, blocks: &'d ast::BlockNodeList<'a>) {
+        let mut pending_notes = Vec::new();
+        for block in blocks {
+            if is_comment_block(block) {
+                pending_notes.extend(block_notes(block, self.note_prefix));
+                continue;
+            }
+            let mut notes = self.inherited_notes.clone();
+            notes.append(&mut pending_notes);
+            let outer_notes = mem::replace(&mut self.inherited_notes, notes);
+            self.visit_block(block);
+            self.inherited_notes = outer_notes;
+        }
+    }
+
+    fn visit_outofband(&mut self, oob: &'d ast::OutOfBandNode<'a>) {
+        // footnotes do not inherit the notes of the blocks before them
+        let outer_notes = mem::take(&mut self.inherited_notes);
+        walk_outofband(self, oob);
+        self.inherited_notes = outer_notes;
+    }
+
+    // sentences do not nest, so there is no need to descend
+    fn visit_inline(&mut self, inline: &'d ast::InlineNode<'a>) {
+        if let ast::InlineNode::Surrounding { tag, .. } = inline {
+            if *tag == ast::SurroundingInlineTag::Custom(crate::sentence_segment::SENTENCE_TAG) {
+                if !self.inherited_notes.is_empty() {
+                    self.notes.insert(self.idx, self.inherited_notes.clone());
+                }
+                self.idx += 1;
+            }
+        }
+    }
+}
+
+impl<'a> clause::DocumentClauseList<'a> {
+    /// Attaches to each clause the notes left for it in `doc`, the segmented document
+    /// this list was extracted from, in comments starting with `note_prefix`.
+    pub fn attach_notes(&mut self, doc: &ast::Document<'_>, note_prefix: &str) {
+        let mut collector = NoteCollector {
+            note_prefix,
+            inherited_notes: Vec::new(),
+            idx: 1,
+            notes: HashMap::new(),
+        };
+        doc.accept(&mut collector);
+        for clause in self.clauses.iter_mut() {
+            if let Some(clause_notes) = collector.notes.remove(&clause.clause_idx) {
+                clause.clause_notes = clause_notes;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{parse, segmented};
+
+    fn first_block(text: &str) -> ast::BlockNode<'_> {
+        parse(text).blocks.into_iter().next().unwrap()
+    }
+
+    #[test]
+    fn comments_are_read() {
+        let block = first_block("<!-- translator: a note --> <!--other-->\n");
+        assert_eq!(
+            html_comments(&block),
+            Some(vec!["translator: a note".to_string(), "other".to_string()])
+        );
+        assert_eq!(block_notes(&block, DEFAULT_NOTE_PREFIX), vec!["a note"]);
+    }
+
+    #[test]
+    fn abruptly_closed_comments_do_not_panic() {
+        assert_eq!(html_comments(&first_block("<!-->\n")), None);
+        assert_eq!(
+            html_comments(&first_block("<!---->\n")),
+            Some(vec![String::new()])
+        );
+    }
+
+    #[test]
+    fn notes_attach_to_the_clauses_of_the_next_block() {
+        let doc = segmented(
+            "<!-- translator: outer -->\n\n> <!-- translator: inner -->\n>\n> One. Two.\n\n\
+             Three.\n",
+        );
+        let mut clause_list = doc.extract_clause_list(&"en-US".into());
+        clause_list.attach_notes(&doc, DEFAULT_NOTE_PREFIX);
+        let notes: Vec<&[String]> = clause_list
+            .clauses
+            .iter()
+            .map(|clause| &clause.clause_notes[..])
+            .collect();
+        assert_eq!(
+            notes,
+            [&["outer", "inner"][..], &["outer", "inner"][..], &[][..]]
+        );
+    }
+}
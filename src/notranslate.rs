@@ -0,0 +1,352 @@
+//! Content marked by its author as not to be translated.
+//!
+//! Blocks between `<!-- mdt:skip -->` and `<!-- mdt:end -->` comments, HTML blocks and
+//! inline HTML elements with a `notranslate` class or `translate="no"` are wrapped in a
+//! `Custom` node named `NO_TRANSLATE_TAG_NAME`. Segmentation leaves such nodes alone, so
+//! they never become clauses, and applying translations copies them through unchanged.
+use crate::ast;
+use crate::visit::{walk_block_mut, VisitorMut};
+use core::mem;
+
+/// The name of the `Custom` tag wrapping content that must not be translated.
+pub const NO_TRANSLATE_TAG_NAME: &str = "NoTranslate";
+
+pub(crate) const NO_TRANSLATE_TAG: pulldown_cmark::CowStr<'static> =
+    pulldown_cmark::CowStr::Borrowed(NO_TRANSLATE_TAG_NAME);
+
+const SKIP_COMMENT: &str = "<!-- mdt:skip -->";
+const END_COMMENT: &str = "<!-- mdt:end -->";
+
+pub(crate) fn is_untranslatable_block(block: &ast::BlockNode<'_>) -> bool {
+    matches!(block, ast::BlockNode::Container { tag: ast::ContainerBlockTag::Custom(name), .. } if *name == NO_TRANSLATE_TAG)
+}
+
+pub(crate) fn is_untranslatable_inline(node: &ast::InlineNode<'_>) -> bool {
+    matches!(node, ast::InlineNode::Surrounding { tag: ast::SurroundingInlineTag::Custom(name), .. } if *name == NO_TRANSLATE_TAG)
+}
+
+/// Returns the element name of `html` if it is an opening tag marked as not to be
+/// translated.
+fn untranslatable_element_name(html: &str) -> Option<&str> {
+    let html = html.trim();
+    let rest = html.strip_prefix('<')?;
+    let name_len = rest
+        .find(|c: char| c.is_whitespace() || c == '>' || c == '/')
+        .unwrap_or(rest.len());
+    let name = &rest[..name_len];
+    if name.is_empty() || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-') {
+        return None;
+    }
+    let tag_end = rest.find('>').unwrap_or(rest.len());
+    let attributes = rest[name_len..tag_end].replace('\'', "\"");
+    let has_class = attributes.split("class=\"").skip(1).any(|value| {
+        value
+            .split('"')
+            .next()
+            .unwrap_or_default()
+            .split_whitespace()
+            .any(|class| class == "notranslate")
+    });
+    if has_class || attributes.contains("translate=\"no\"") {
+        Some(name)
+    } else {
+        None
+    }
+}
+
+fn is_opening_tag(html: &str, name: &str) -> bool {
+    html.trim()
+        .strip_prefix('<')
+        .and_then(|rest| rest.strip_prefix(name))
+        .is_some_and(|rest| rest.starts_with(|c: char| c.is_whitespace() || c == '>' || c == '/'))
+}
+
+fn is_closing_tag(html: &str, name: &str) -> bool {
+    html.trim()
+        .strip_prefix("</")
+        .and_then(|rest| rest.strip_prefix(name))
+        .is_some_and(|rest| rest.trim_start().starts_with('>'))
+}
+
+fn html_block_text(block: &ast::BlockNode<'_>) -> Option<String> {
+    match block {
+        ast::BlockNode::Leaf {
+            tag: ast::LeafBlockTag::Html,
+            contents,
+            ..
+        } => {
+            let mut text = String::new();
+            for node in contents {
+                if let ast::InlineNode::Content {
+                    tag: ast::ContentInlineTag::RawHtml(s),
+                    ..
+                } = node
+                {
+                    text += s;
+                }
+            }
+            Some(text)
+        }
+        _ => None,
+    }
+}
+
+/// Where an untranslatable region of blocks ends.
+enum RegionEnd {
+    /// The region is the starting block alone.
+    Here,
+    EndComment,
+    ClosingTag(String),
+}
+
+impl RegionEnd {
+    fn is_end(&self, text: &str) -> bool {
+        match self {
+            RegionEnd::Here => true,
+            RegionEnd::EndComment => text.trim() == END_COMMENT,
+            RegionEnd::ClosingTag(name) => {
+                is_closing_tag(text.lines().next().unwrap_or_default(), name)
+            }
+        }
+    }
+}
+
+/// Decides whether an untranslatable region starts at `block`.
+fn region_start(block: &ast::BlockNode<'_>) -> Option<RegionEnd> {
+    let text = html_block_text(block)?;
+    let text = text.trim();
+    if text == SKIP_COMMENT {
+        return Some(RegionEnd::EndComment);
+    }
+    let name = untranslatable_element_name(text)?;
+    if text.ends_with(&format!("</{}>", name)) {
+        Some(RegionEnd::Here)
+    } else {
+        Some(RegionEnd::ClosingTag(name.to_string()))
+    }
+}
+
+/// Wraps each untranslatable region of `blocks`, without descending into them.
+fn wrap_untranslatable_blocks<'a>(blocks: &mut ast::BlockNodeList<'a>) {
+    let input = mem::take(blocks);
+    let mut input = input.into_iter();
+    while let Some(block) = input.next() {
+        let region_end = match region_start(&block) {
+            Some(region_end) => region_end,
+            None => {
+                blocks.push(block);
+                continue;
+            }
+        };
+        let mut children = vec![block];
+        if !matches!(region_end, RegionEnd::Here) {
+            // a region missing its end runs to the end of the enclosing block
+            for block in input.by_ref() {
+                let is_end = html_block_text(&block).is_some_and(|text| region_end.is_end(&text));
+                children.push(block);
+                if is_end {
+                    break;
+                }
+            }
+        }
+        let span = children
+            .iter()
+            .fold(ast::SourceSpan::default(), |span, block| {
+                span.cover(block.span())
+            });
+        blocks.push(ast::BlockNode::Container {
+            tag: ast::ContainerBlockTag::Custom(NO_TRANSLATE_TAG),
+            children,
+            span,
+        });
+    }
+}
+
+fn raw_html<'n>(node: &'n ast::InlineNode<'_>) -> Option<&'n str> {
+    match node {
+        ast::InlineNode::Content {
+            tag: ast::ContentInlineTag::RawHtml(s),
+            ..
+        } => Some(s),
+        _ => None,
+    }
+}
+
+/// Wraps the inline elements marked as not to be translated, from their opening tag to
+/// their closing tag inclusive.
+pub(crate) fn mark_inlines(inlines: &mut ast::InlineNodeList<'_>) {
+    let input = mem::take(inlines);
+    let mut input = input.into_iter();
+    while let Some(mut node) = input.next() {
+        let name = match raw_html(&node).and_then(untranslatable_element_name) {
+            Some(name) => name.to_string(),
+            None => {
+                let is_marked = is_untranslatable_inline(&node);
+                if let ast::InlineNode::Surrounding { contents, .. } = &mut node {
+                    if !is_marked {
+                        mark_inlines(contents);
+                    }
+                }
+                inlines.push(node);
+                continue;
+            }
+        };
+        let mut contents = vec![node];
+        let mut depth = 1;
+        for node in input.by_ref() {
+            if let Some(html) = raw_html(&node) {
+                if is_opening_tag(html, &name) {
+                    depth += 1;
+                } else if is_closing_tag(html, &name) {
+                    depth -= 1;
+                }
+            }
+            contents.push(node);
+            if depth == 0 {
+                break;
+            }
+        }
+        inlines.push(ast::InlineNode::Surrounding {
+            tag: ast::SurroundingInlineTag::Custom(NO_TRANSLATE_TAG),
+            span: ast::SourceSpan::covering_inlines(&contents),
+            contents,
+        });
+    }
+}
+
+/// Wraps the untranslatable content of a document, leaving what is already wrapped alone.
+struct UntranslatableMarker;
+
+impl<'a> VisitorMut<'a> for UntranslatableMarker {
+    fn visit_blocks_mut(&mut self, blocks: &mut ast::BlockNodeList<'a>) {
+        wrap_untranslatable_blocks(blocks);
+        for block in blocks.iter_mut() {
+            self.visit_block_mut(block);
+        }
+    }
+
+    fn visit_block_mut(&mut self, block: &mut ast::BlockNode<'a>) {
+        if !is_untranslatable_block(block) {
+            walk_block_mut(self, block);
+        }
+    }
+
+    // `mark_inlines` descends into the inlines by itself
+    fn visit_inlines_mut(&mut self, inlines: &mut ast::InlineNodeList<'a>) {
+        mark_inlines(inlines);
+    }
+}
+
+fn collect_untranslatable_inlines<'a>(
+    inlines: &ast::InlineNodeList<'a>,
+    output: &mut Vec<ast::InlineNode<'a>>,
+) {
+    for node in inlines {
+        if is_untranslatable_inline(node) {
+            output.push(node.clone());
+        } else if let ast::InlineNode::Surrounding { contents, .. } = node {
+            collect_untranslatable_inlines(contents, output);
+        }
+    }
+}
+
+fn replace_untranslatable_inlines<'a>(
+    inlines: &mut ast::InlineNodeList<'a>,
+    originals: &mut impl Iterator<Item = ast::InlineNode<'a>>,
+) {
+    for node in inlines.iter_mut() {
+        if is_untranslatable_inline(node) {
+            if let Some(original) = originals.next() {
+                *node = original;
+            }
+        } else if let ast::InlineNode::Surrounding { contents, .. } = node {
+            replace_untranslatable_inlines(contents, originals);
+        }
+    }
+}
+
+/// Puts back the untranslatable elements of `source` into its translation, in order, so
+/// that whatever the translator did to them is undone.
+pub(crate) fn restore_untranslatable<'a>(
+    source: &ast::InlineNodeList<'a>,
+    translation: &mut ast::InlineNodeList<'a>,
+) {
+    let mut originals = Vec::new();
+    collect_untranslatable_inlines(source, &mut originals);
+    if originals.is_empty() {
+        return;
+    }
+    mark_inlines(translation);
+    replace_untranslatable_inlines(translation, &mut originals.into_iter());
+}
+
+impl<'a> ast::Document<'a> {
+    /// Wraps the content marked as not to be translated in `NO_TRANSLATE_TAG_NAME` nodes.
+    ///
+    /// This is done by `perform_sentence_segment`, and does nothing on content already
+    /// wrapped.
+    pub fn mark_untranslatable(&mut self) {
+        self.accept_mut(&mut UntranslatableMarker);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{clause_list, parse, segmented, translate};
+
+    #[test]
+    fn marked_content_is_wrapped() {
+        let text = "<!-- mdt:skip -->\n\nSkipped.\n\n<!-- mdt:end -->\n\n\
+                    > Keep <code translate=\"no\">this</code> here.\n";
+        let mut doc = parse(text);
+        doc.mark_untranslatable();
+        assert_eq!(doc.blocks.len(), 2);
+        assert!(is_untranslatable_block(&doc.blocks[0]));
+        let contents = match &doc.blocks[1] {
+            ast::BlockNode::Container { children, .. } => match &children[0] {
+                ast::BlockNode::Leaf { contents, .. } => contents,
+                _ => panic!("not a paragraph"),
+            },
+            _ => panic!("not a blockquote"),
+        };
+        assert!(contents.iter().any(is_untranslatable_inline));
+
+        // marking twice changes nothing
+        let blocks = format!("{:?}", doc.blocks);
+        doc.mark_untranslatable();
+        assert_eq!(format!("{:?}", doc.blocks), blocks);
+    }
+
+    #[test]
+    fn marked_content_is_not_extracted() {
+        let clause_list = clause_list(
+            "<!-- mdt:skip -->\n\nSkipped.\n\n<!-- mdt:end -->\n\n\
+             <div class=\"notranslate\">\n\nAlso skipped.\n\n</div>\n\nKept.\n",
+        );
+        assert_eq!(clause_list.clauses.len(), 1);
+        assert_eq!(clause_list.clauses[0].source_text(), "Kept.");
+    }
+
+    #[test]
+    fn translations_cannot_change_marked_elements() {
+        let source = "Run <code translate=\"no\">ls</code> now.\n";
+        let mut doc = segmented(source);
+        let mut clause_list = doc.extract_clause_list(&"en-US".into()).into_owned();
+        translate(
+            &mut clause_list,
+            1,
+            "zh-CN",
+            "现在运行 <code translate=\"no\">列表</code>。",
+        );
+        let spliced = doc
+            .splice_translations(source, &clause_list, "zh-CN")
+            .unwrap();
+        assert_eq!(spliced, "现在运行 <code translate=\"no\">ls</code>。\n");
+
+        doc.apply_translations(&clause_list, "zh-CN");
+        let mut output = String::new();
+        pulldown_cmark_to_cmark::cmark(doc.into_tokens(), &mut output, None).unwrap();
+        assert_eq!(output, "现在运行 <code translate=\"no\">ls</code>。");
+    }
+}
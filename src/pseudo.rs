@@ -0,0 +1,100 @@
+//! Pseudo-localization, for testing layouts and i18n plumbing without translations.
+//!
+//! Every clause becomes accented text, padded by about a quarter of its length and
+//! wrapped in brackets, e.g. `[Ŧĥîš îš ŧêxŧ ~~~]`. Untranslated text then stands out,
+//! and so does truncated text.
+use crate::translator::{TranslationUnit, Translator, TranslatorError};
+
+/// The language tag conventionally used for pseudo-localized output.
+pub const PSEUDO_LANGUAGE: &str = "qps-ploc";
+
+const LOWERCASE: &str = "åƀçđêƒĝĥîĵķĺɱñöþǫŕšŧûṽŵxýž";
+const UPPERCASE: &str = "ÅƁÇĐÊƑĜĤÎĴĶĹṀÑÖÞǪŔŠŦÛṼŴẊÝŽ";
+
+fn accent(c: char) -> char {
+    let (table, base) = match c {
+        'a'..='z' => (LOWERCASE, 'a'),
+        'A'..='Z' => (UPPERCASE, 'A'),
+        _ => return c,
+    };
+    table
+        .chars()
+        .nth(c as usize - base as usize)
+        .expect("table covers the alphabet")
+}
+
+/// Pseudo-localizes `text`, leaving the translator placeholders in it untouched. Text
+/// made of placeholders only, such as a raw HTML block, is returned as is.
+pub fn pseudolocalize(text: &str) -> String {
+    let mut output = String::from("[");
+    let mut letter_count = 0usize;
+    let mut in_placeholder = false;
+    for c in text.chars() {
+        match c {
+            '⟦' => in_placeholder = true,
+            '⟧' => in_placeholder = false,
+            _ if in_placeholder => {}
+            c if c.is_alphanumeric() => letter_count += 1,
+            _ => {}
+        }
+        output.push(if in_placeholder { c } else { accent(c) });
+    }
+    if letter_count == 0 {
+        return text.to_string();
+    }
+    output.push(' ');
+    output.extend(core::iter::repeat_n('~', letter_count.div_ceil(4)));
+    output.push(']');
+    output
+}
+
+/// A backend producing pseudo-localized text instead of translations.
+#[derive(Clone, Copy, Default, Debug)]
+pub struct PseudoTranslator;
+
+impl Translator for PseudoTranslator {
+    fn translate(
+        &mut self,
+        _source_language: &str,
+        _target_language: &str,
+        units: &[TranslationUnit],
+    ) -> Result<Vec<String>, TranslatorError> {
+        Ok(units
+            .iter()
+            .map(|unit| pseudolocalize(&unit.text))
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::clause_list;
+
+    #[test]
+    fn letters_are_accented_and_padded() {
+        assert_eq!(pseudolocalize("This is text."), "[Ŧĥîš îš ŧêxŧ. ~~~]");
+        assert_eq!(pseudolocalize("Zaz 42"), "[Žåž 42 ~~]");
+    }
+
+    #[test]
+    fn placeholders_are_left_alone() {
+        assert_eq!(
+            pseudolocalize("Run ⟦1⟧ in ⟦2⟧the⟦/2⟧."),
+            "[Ŕûñ ⟦1⟧ îñ ⟦2⟧ŧĥê⟦/2⟧. ~~]"
+        );
+        assert_eq!(pseudolocalize("⟦1⟧"), "⟦1⟧");
+    }
+
+    #[test]
+    fn prefilled_translations_keep_their_markup() {
+        let mut clause_list = clause_list("Run `ls` *now*.\n");
+        clause_list
+            .prefill_translations(&mut PseudoTranslator, "en-US", PSEUDO_LANGUAGE, 10)
+            .unwrap();
+        let contents = clause_list.clauses[0].translation(PSEUDO_LANGUAGE).unwrap();
+        let mut text = String::new();
+        crate::textualize::textualize_inline_list(contents, &mut text);
+        assert_eq!(text, "[Ŕûñ (code) (ñöŵ). ~~]");
+    }
+}
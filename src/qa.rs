@@ -0,0 +1,403 @@
+//! Quality checks comparing each translation with the source of its clause.
+use crate::ast;
+use crate::clause;
+use crate::textualize;
+use crate::visit::{walk_inline, Visitor};
+use core::fmt;
+
+/// The parts of a clause the checks look at.
+#[derive(Default)]
+struct ClauseParts {
+    text: String,
+    urls: Vec<String>,
+    codes: Vec<String>,
+}
+
+/// Collects the link destinations and inline code of a clause.
+struct PartCollector<'p> {
+    parts: &'p mut ClauseParts,
+}
+
+impl<'d, 'a> Visitor<'d, 'a> for PartCollector<'_> {
+    fn visit_inline(&mut self, node: &'d ast::InlineNode<'a>) {
+        match node {
+            ast::InlineNode::Surrounding { tag, .. } => {
+                match tag {
+                    ast::SurroundingInlineTag::Link(_, url, _)
+                    | ast::SurroundingInlineTag::Image(_, url, _) => {
+                        self.parts.urls.push(url.to_string())
+                    }
+                    _ => {}
+                }
+                walk_inline(self, node);
+            }
+            ast::InlineNode::Content {
+                tag: ast::ContentInlineTag::Code(s),
+                ..
+            } => self.parts.codes.push(s.to_string()),
+            ast::InlineNode::Content { .. } => {}
+        }
+    }
+}
+
+fn clause_parts(inlines: &[ast::InlineNode<'_>]) -> ClauseParts {
+    let mut parts = ClauseParts {
+        text: textualize::plain_text(inlines),
+        ..ClauseParts::default()
+    };
+    let mut collector = PartCollector { parts: &mut parts };
+    for inline in inlines {
+        collector.visit_inline(inline);
+    }
+    parts
+}
+
+/// Characters grouping the digits of a number, or separating its decimals.
+fn is_digit_separator(c: char) -> bool {
+    matches!(
+        c,
+        ',' | '.' | '\'' | '_' | '\u{a0}' | '\u{2009}' | '\u{202f}'
+    )
+}
+
+/// The numbers in `text` as runs of digits. A separator between two digits is skipped,
+/// so that `1,000`, `1.000` and `1000` are taken for the same number.
+fn numbers(text: &str) -> Vec<String> {
+    let chars: Vec<char> = text.chars().collect();
+    let mut numbers = Vec::new();
+    let mut number = String::new();
+    for (i, &c) in chars.iter().enumerate() {
+        if c.is_ascii_digit() {
+            number.push(c);
+            continue;
+        }
+        let is_separator = is_digit_separator(c)
+            && !number.is_empty()
+            && chars.get(i + 1).is_some_and(char::is_ascii_digit);
+        if !is_separator && !number.is_empty() {
+            numbers.push(core::mem::take(&mut number));
+        }
+    }
+    if !number.is_empty() {
+        numbers.push(number);
+    }
+    numbers
+}
+
+/// Returns the items of `source` missing from `target`, then those of `target` not in
+/// `source`, counting repeated items.
+fn difference(source: &[String], target: &[String]) -> (Vec<String>, Vec<String>) {
+    let mut extra = target.to_vec();
+    let mut missing = Vec::new();
+    for item in source {
+        match extra.iter().position(|other| other == item) {
+            Some(pos) => {
+                extra.remove(pos);
+            }
+            None => missing.push(item.clone()),
+        }
+    }
+    (missing, extra)
+}
+
+/// The punctuation ending `text`, with full-width forms folded to ASCII.
+fn trailing_punctuation(text: &str) -> Option<char> {
+    let c = text.trim_end().chars().last()?;
+    let c = match c {
+        '。' | '．' => '.',
+        '，' | '、' => ',',
+        '！' => '!',
+        '？' => '?',
+        '：' => ':',
+        '；' => ';',
+        c => c,
+    };
+    if matches!(c, '.' | ',' | '!' | '?' | ':' | ';') {
+        Some(c)
+    } else {
+        None
+    }
+}
+
+const PAIRS: &[(char, char)] = &[
+    ('(', ')'),
+    ('[', ']'),
+    ('{', '}'),
+    ('“', '”'),
+    ('«', '»'),
+    ('「', '」'),
+    ('『', '』'),
+    ('（', '）'),
+];
+
+/// The first pair of brackets or quotes left unbalanced in `text`.
+fn unbalanced_pair(text: &str) -> Option<(char, char)> {
+    if text.chars().filter(|&c| c == '"').count() % 2 != 0 {
+        return Some(('"', '"'));
+    }
+    PAIRS.iter().copied().find(|&(open, close)| {
+        let mut depth = 0i32;
+        for c in text.chars() {
+            if c == open {
+                depth += 1;
+            } else if c == close {
+                depth -= 1;
+                if depth < 0 {
+                    return true;
+                }
+            }
+        }
+        depth != 0
+    })
+}
+
+/// Whether `text` starts and ends with whitespace. Plain spaces are left out, as a
+/// sentence keeps the space separating it from the next one while a translation read
+/// from a clause file is always trimmed.
+fn edge_whitespace(text: &str) -> (bool, bool) {
+    let is_edge = |c: char| c.is_whitespace() && c != ' ';
+    let text = text.trim_matches(' ');
+    (text.starts_with(is_edge), text.ends_with(is_edge))
+}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum QaIssue {
+    /// Numbers of the source missing from the translation, and numbers added to it.
+    Numbers {
+        missing: Vec<String>,
+        extra: Vec<String>,
+    },
+    /// Link and image destinations that differ.
+    Urls {
+        missing: Vec<String>,
+        extra: Vec<String>,
+    },
+    /// Inline code that differs.
+    Code {
+        missing: Vec<String>,
+        extra: Vec<String>,
+    },
+    /// The translation is the source text itself.
+    Untranslated,
+    /// The translation has two spaces in a row where the source has none.
+    DoubledSpace,
+    /// The source and the translation end with different punctuation.
+    TrailingPunctuation {
+        source: Option<char>,
+        target: Option<char>,
+    },
+    /// The translation leaves a pair of brackets or quotes unbalanced.
+    Unbalanced { open: char, close: char },
+    /// The source and the translation differ in leading or trailing whitespace.
+    Whitespace,
+}
+
+fn write_list(f: &mut fmt::Formatter<'_>, label: &str, items: &[String]) -> fmt::Result {
+    if !items.is_empty() {
+        let quoted: Vec<String> = items.iter().map(|item| format!("`{}`", item)).collect();
+        write!(f, ", {} {}", label, quoted.join(" "))?;
+    }
+    Ok(())
+}
+
+fn write_punctuation(f: &mut fmt::Formatter<'_>, c: Option<char>) -> fmt::Result {
+    match c {
+        Some(c) => write!(f, "`{}`", c),
+        None => write!(f, "none"),
+    }
+}
+
+impl fmt::Display for QaIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            QaIssue::Numbers { missing, extra }
+            | QaIssue::Urls { missing, extra }
+            | QaIssue::Code { missing, extra } => {
+                let what = match self {
+                    QaIssue::Numbers { .. } => "numbers",
+                    QaIssue::Urls { .. } => "urls",
+                    _ => "inline code",
+                };
+                write!(f, "{} differ", what)?;
+                write_list(f, "missing", missing)?;
+                write_list(f, "unexpected", extra)
+            }
+            QaIssue::Untranslated => write!(f, "translation is identical to the source"),
+            QaIssue::DoubledSpace => write!(f, "doubled space"),
+            QaIssue::TrailingPunctuation { source, target } => {
+                write!(f, "trailing punctuation differs: source ")?;
+                write_punctuation(f, *source)?;
+                write!(f, ", translation ")?;
+                write_punctuation(f, *target)
+            }
+            QaIssue::Unbalanced { open, close } => {
+                write!(f, "unbalanced `{}` and `{}`", open, close)
+            }
+            QaIssue::Whitespace => write!(f, "leading or trailing whitespace differs"),
+        }
+    }
+}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct QaDiagnostic {
+    pub clause_idx: usize,
+    pub issue: QaIssue,
+}
+
+impl fmt::Display for QaDiagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "clause {}: {}", self.clause_idx, self.issue)
+    }
+}
+
+/// Compares a translation with its source, returning the issues found in order of the
+/// checks.
+pub fn check_translation(
+    source: &[ast::InlineNode<'_>],
+    translation: &[ast::InlineNode<'_>],
+) -> Vec<QaIssue> {
+    let source = clause_parts(source);
+    let target = clause_parts(translation);
+    let mut issues = Vec::new();
+
+    let (missing, extra) = difference(&numbers(&source.text), &numbers(&target.text));
+    if !missing.is_empty() || !extra.is_empty() {
+        issues.push(QaIssue::Numbers { missing, extra });
+    }
+    let (missing, extra) = difference(&source.urls, &target.urls);
+    if !missing.is_empty() || !extra.is_empty() {
+        issues.push(QaIssue::Urls { missing, extra });
+    }
+    let (missing, extra) = difference(&source.codes, &target.codes);
+    if !missing.is_empty() || !extra.is_empty() {
+        issues.push(QaIssue::Code { missing, extra });
+    }
+    if source.text.trim() == target.text.trim() && source.text.chars().any(char::is_alphabetic) {
+        issues.push(QaIssue::Untranslated);
+    }
+    if target.text.contains("  ") && !source.text.contains("  ") {
+        issues.push(QaIssue::DoubledSpace);
+    }
+    let source_punctuation = trailing_punctuation(&source.text);
+    let target_punctuation = trailing_punctuation(&target.text);
+    if source_punctuation != target_punctuation {
+        issues.push(QaIssue::TrailingPunctuation {
+            source: source_punctuation,
+            target: target_punctuation,
+        });
+    }
+    if unbalanced_pair(&source.text).is_none() {
+        if let Some((open, close)) = unbalanced_pair(&target.text) {
+            issues.push(QaIssue::Unbalanced { open, close });
+        }
+    }
+    if edge_whitespace(&source.text) != edge_whitespace(&target.text) {
+        issues.push(QaIssue::Whitespace);
+    }
+    issues
+}
+
+impl<'a> clause::DocumentClauseList<'a> {
+    /// Runs the quality checks over each translation into `language`. Untranslated
+    /// clauses are skipped.
+    pub fn check_translations(&self, language: &str) -> Vec<QaDiagnostic> {
+        let mut diagnostics = Vec::new();
+        for clause in &self.clauses {
+            let translation = match clause.translation(language) {
+                Some(translation) if !translation.is_empty() => translation,
+                _ => continue,
+            };
+            for issue in check_translation(&clause.clause_contents, translation) {
+                diagnostics.push(QaDiagnostic {
+                    clause_idx: clause.clause_idx,
+                    issue,
+                });
+            }
+        }
+        diagnostics
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::inlines;
+
+    fn check(source: &str, translation: &str) -> Vec<QaIssue> {
+        check_translation(&inlines(source), &inlines(translation))
+    }
+
+    #[test]
+    fn faithful_translations_pass() {
+        assert_eq!(
+            check(
+                "Run `cargo build` ([docs](https://example.com)) 2 times.",
+                "运行 `cargo build`（[文档](https://example.com)）2 次。"
+            ),
+            vec![]
+        );
+    }
+
+    #[test]
+    fn differences_are_reported() {
+        assert_eq!(
+            check(
+                "Wait 10 seconds `now`.",
+                "Attendez 1O secondes  `maintenant`"
+            ),
+            vec![
+                QaIssue::Numbers {
+                    missing: vec!["10".to_string()],
+                    extra: vec!["1".to_string()],
+                },
+                QaIssue::Code {
+                    missing: vec!["now".to_string()],
+                    extra: vec!["maintenant".to_string()],
+                },
+                QaIssue::DoubledSpace,
+                QaIssue::TrailingPunctuation {
+                    source: Some('.'),
+                    target: None,
+                },
+            ]
+        );
+        assert_eq!(
+            check("See [here](a).", "Voir [ici](b)."),
+            vec![QaIssue::Urls {
+                missing: vec!["a".to_string()],
+                extra: vec!["b".to_string()],
+            }]
+        );
+    }
+
+    #[test]
+    fn digit_grouping_is_ignored() {
+        assert_eq!(
+            check(
+                "It costs 1,000 or 2.5 coins.",
+                "Cela coûte 1000 ou 2,5 pièces."
+            ),
+            vec![]
+        );
+        assert_eq!(
+            numbers("1, 2 and 3,000.5."),
+            ["1", "2", "30005"].map(String::from)
+        );
+    }
+
+    #[test]
+    fn copies_unbalanced_pairs_and_edge_whitespace_are_reported() {
+        assert_eq!(
+            check("Hello world.", "Hello world."),
+            vec![QaIssue::Untranslated]
+        );
+        assert_eq!(
+            check("Hello (world).", "你好（世界。"),
+            vec![QaIssue::Unbalanced {
+                open: '（',
+                close: '）'
+            }]
+        );
+        assert_eq!(check("Hello.\u{a0}", "Bonjour."), vec![QaIssue::Whitespace]);
+    }
+}
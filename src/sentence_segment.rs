@@ -1,8 +1,13 @@
 use crate::ast;
+use crate::visit::{walk_block_mut, walk_outofband_mut, VisitorMut};
 use core::mem;
+use core::ops::Range;
+
+/// The name of the `Custom` tag wrapping each sentence after segmentation.
+pub const SENTENCE_TAG_NAME: &str = "Sentence";
 
 pub(crate) const SENTENCE_TAG: pulldown_cmark::CowStr<'static> =
-    pulldown_cmark::CowStr::Borrowed("Sentence");
+    pulldown_cmark::CowStr::Borrowed(SENTENCE_TAG_NAME);
 
 fn cowstr_substr<'a>(
     cowstr: &pulldown_cmark::CowStr<'a>,
@@ -16,275 +21,466 @@ fn cowstr_substr<'a>(
     }
 }
 
-fn split_text<'a>(
-    input: &pulldown_cmark::CowStr<'a>,
-    output: &mut ast::InlineNodeList<'a>,
-    proposed_split_positions: &[usize],
-    accumulative_length: usize,
-) {
-    let total_len = input.as_ref().len();
-    let split_pos_iter = proposed_split_positions
-        .iter()
-        .take_while(|pos| accumulative_length + *pos < total_len);
-    let mut start_pos = 0;
-    for &split_pos in split_pos_iter {
-        let split_pos = split_pos.checked_sub(accumulative_length).unwrap();
-        let new_text = cowstr_substr(&input, start_pos..split_pos);
-        output.push(ast::InlineNode::Content {
-            tag: ast::ContentInlineTag::Text(new_text),
+/// Computes the span of `input[range]`, given the span of the whole `input`.
+///
+/// The text of an event may differ from its source when escapes or entities are
+/// involved, in which case the resulting span is clamped to the original one.
+fn text_subspan(
+    input: &str,
+    span: &ast::SourceSpan,
+    range: core::ops::Range<usize>,
+) -> ast::SourceSpan {
+    if !span.is_known() {
+        return ast::SourceSpan::default();
+    }
+    let map_offset = |pos: usize| {
+        if pos == input.len() {
+            span.range.end
+        } else {
+            (span.range.start + pos).min(span.range.end)
+        }
+    };
+    ast::SourceSpan {
+        range: map_offset(range.start)..map_offset(range.end),
+        start: span.start.advance(&input[..range.start]),
+        end: span.start.advance(&input[..range.end]),
+    }
+}
+
+/// The text range of an inline node within the textualized leaf, see `map_offsets`.
+struct OffsetEntry {
+    range: Range<usize>,
+    /// The index following the entries of the node and its descendants.
+    subtree_end: usize,
+}
+
+fn is_splittable_tag(tag: &ast::SurroundingInlineTag<'_>) -> bool {
+    matches!(
+        tag,
+        ast::SurroundingInlineTag::Emphasis
+            | ast::SurroundingInlineTag::Strong
+            | ast::SurroundingInlineTag::Strikethrough
+    )
+}
+
+/// Textualizes `inlines` like `textualize_inline_list`, also recording the text range of
+/// each node in document order. The nodes that sentences may split, text and emphasis,
+/// are descended into; the others are atomic and get a single entry.
+fn map_offsets(inlines: &ast::InlineNodeList<'_>, text: &mut String, map: &mut Vec<OffsetEntry>) {
+    use crate::textualize::textualize_inline_node;
+    for node in inlines {
+        let idx = map.len();
+        let start = text.len();
+        map.push(OffsetEntry {
+            range: start..start,
+            subtree_end: idx + 1,
         });
-        start_pos = split_pos;
+        match node {
+            ast::InlineNode::Surrounding { tag, contents, .. } if is_splittable_tag(tag) => {
+                text.push('(');
+                map_offsets(contents, text, map);
+                text.push(')');
+            }
+            node => textualize_inline_node(node, text),
+        }
+        map[idx].range.end = text.len();
+        map[idx].subtree_end = map.len();
     }
-    let rest_text = cowstr_substr(&input, start_pos..total_len);
-    output.push(ast::InlineNode::Content {
-        tag: ast::ContentInlineTag::Text(rest_text),
-    });
 }
 
-fn split_inline_node<'a>(
-    mut input: ast::InlineNode<'a>,
-    output: &mut ast::InlineNodeList<'a>,
-    proposed_split_positions: &mut &[usize],
-    accumulative_length: &mut usize,
-) {
-    use crate::textualize::*;
-    let mut textualize_result = String::new();
-    'custom_processing: loop {
-        let mut splitted_nodes = Vec::new();
-        match &mut input {
-            ast::InlineNode::Surrounding { tag, contents } => match tag {
-                ast::SurroundingInlineTag::Emphasis
-                | ast::SurroundingInlineTag::Strong
-                | ast::SurroundingInlineTag::Strikethrough => {
-                    let mut inner_proposed_split_positions = *proposed_split_positions;
-                    let mut inner_accumulative_length = *accumulative_length;
-                    split_inlines(
-                        contents,
-                        &mut inner_proposed_split_positions,
-                        &mut inner_accumulative_length,
-                    );
-                    regroup_inlines(
-                        mem::take(contents),
-                        &mut splitted_nodes,
-                        |nodes| ast::InlineNode::Surrounding {
-                            tag: tag.clone(),
-                            contents: nodes,
-                        },
-                        |node| {
-                            if let ast::InlineNode::Surrounding {
-                                tag: current_tag, ..
-                            } = node
-                            {
-                                current_tag == tag
-                            } else {
-                                false
-                            }
-                        },
-                        *proposed_split_positions,
-                        *accumulative_length,
-                    );
-                }
-                ast::SurroundingInlineTag::Link(_, _, _)
-                | ast::SurroundingInlineTag::Image(_, _, _)
-                | ast::SurroundingInlineTag::Custom(_) => {
-                    textualize_inline_node(&input, &mut textualize_result);
-                    output.push(input);
-                    break 'custom_processing;
-                }
-            },
-            ast::InlineNode::Content { tag } => match tag {
-                ast::ContentInlineTag::Text(s) => {
-                    let inner_proposed_split_positions = *proposed_split_positions;
-                    let inner_accumulative_length = *accumulative_length;
-                    split_text(
-                        s,
-                        &mut splitted_nodes,
-                        inner_proposed_split_positions,
-                        inner_accumulative_length,
-                    );
+struct OpenElement<'a> {
+    tag: ast::SurroundingInlineTag<'a>,
+    contents: ast::InlineNodeList<'a>,
+    /// Whether a sentence boundary fell within the element while it was open.
+    is_split: bool,
+}
+
+/// Gathers the nodes of a leaf into sentences. The elements enclosing the current
+/// position are kept open, so that at a sentence boundary each of them is closed and
+/// then reopened in the next sentence.
+#[derive(Default)]
+struct SentenceBuilder<'a> {
+    sentences: ast::InlineNodeList<'a>,
+    current: ast::InlineNodeList<'a>,
+    open_elements: Vec<OpenElement<'a>>,
+}
+
+fn wrap_sentence(contents: ast::InlineNodeList<'_>) -> ast::InlineNode<'_> {
+    if let [ast::InlineNode::Surrounding {
+        tag: ast::SurroundingInlineTag::Custom(name),
+        ..
+    }] = &contents[..]
+    {
+        if *name == SENTENCE_TAG {
+            // already segmented
+            return contents.into_iter().next().unwrap();
+        }
+    }
+    ast::InlineNode::Surrounding {
+        tag: ast::SurroundingInlineTag::Custom(SENTENCE_TAG),
+        span: ast::SourceSpan::covering_inlines(&contents),
+        contents,
+    }
+}
+
+/// Splits off the whitespace and line breaks ending `contents`, which would keep an
+/// emphasis closed right after them from being recognized.
+fn take_trailing_whitespace<'a>(contents: &mut ast::InlineNodeList<'a>) -> ast::InlineNodeList<'a> {
+    let mut trailing = Vec::new();
+    while let Some(node) = contents.pop() {
+        match node {
+            ast::InlineNode::Content {
+                tag: ast::ContentInlineTag::SoftBreak,
+                ..
+            }
+            | ast::InlineNode::Content {
+                tag: ast::ContentInlineTag::HardBreak,
+                ..
+            } => trailing.push(node),
+            ast::InlineNode::Content {
+                tag: ast::ContentInlineTag::Text(s),
+                span,
+            } => {
+                let trimmed_len = s.trim_end().len();
+                if trimmed_len < s.len() {
+                    trailing.push(ast::InlineNode::Content {
+                        tag: ast::ContentInlineTag::Text(cowstr_substr(&s, trimmed_len..s.len())),
+                        span: text_subspan(&s, &span, trimmed_len..s.len()),
+                    });
                 }
-                ast::ContentInlineTag::Code(_)
-                | ast::ContentInlineTag::RawHtml(_)
-                | ast::ContentInlineTag::FootnoteRef(_)
-                | ast::ContentInlineTag::TaskListMarker(_)
-                | ast::ContentInlineTag::SoftBreak
-                | ast::ContentInlineTag::HardBreak
-                | ast::ContentInlineTag::Custom(_) => {
-                    textualize_inline_node(&input, &mut textualize_result);
-                    output.push(input);
-                    break 'custom_processing;
+                if trimmed_len > 0 {
+                    contents.push(ast::InlineNode::Content {
+                        tag: ast::ContentInlineTag::Text(cowstr_substr(&s, 0..trimmed_len)),
+                        span: text_subspan(&s, &span, 0..trimmed_len),
+                    });
+                    break;
                 }
-            },
+            }
+            node => {
+                contents.push(node);
+                break;
+            }
         }
-        textualize_inline_list(&splitted_nodes, &mut textualize_result);
-        output.extend(splitted_nodes);
-        break 'custom_processing;
     }
-    let new_accumulative_length = *accumulative_length + textualize_result.len();
-    let mut last_skipped_index = None;
-    for (idx, pos) in proposed_split_positions.iter().enumerate() {
-        if *pos <= new_accumulative_length {
-            last_skipped_index = Some(idx)
-        } else {
-            break;
+    trailing.reverse();
+    trailing
+}
+
+impl<'a> SentenceBuilder<'a> {
+    fn innermost(&mut self) -> &mut ast::InlineNodeList<'a> {
+        match self.open_elements.last_mut() {
+            Some(element) => &mut element.contents,
+            None => &mut self.current,
         }
     }
-    if let Some(last_skipped_index) = last_skipped_index {
-        *proposed_split_positions = &proposed_split_positions[last_skipped_index + 1..]
+
+    fn push(&mut self, node: ast::InlineNode<'a>) {
+        self.innermost().push(node);
     }
-    *accumulative_length = new_accumulative_length;
-}
 
-fn split_inlines<'a>(
-    inlines: &mut ast::InlineNodeList<'a>,
-    proposed_split_positions: &mut &[usize],
-    accumulative_length: &mut usize,
-) {
-    if proposed_split_positions.is_empty() {
-        return;
+    fn open(&mut self, tag: ast::SurroundingInlineTag<'a>) {
+        self.open_elements.push(OpenElement {
+            tag,
+            contents: Vec::new(),
+            is_split: false,
+        });
     }
-    let input = mem::take(inlines);
-    for inline in input {
-        let mut split_result = Vec::new();
-        split_inline_node(
-            inline,
-            &mut split_result,
-            proposed_split_positions,
-            accumulative_length,
-        );
-        inlines.extend(split_result);
+
+    /// Closes the innermost element, which keeps `span` unless it was split.
+    fn close(&mut self, span: ast::SourceSpan) {
+        let element = self.open_elements.pop().expect("an element is open");
+        if !element.is_split {
+            self.push(ast::InlineNode::Surrounding {
+                tag: element.tag,
+                contents: element.contents,
+                span,
+            });
+        } else if !element.contents.is_empty() {
+            self.push(ast::InlineNode::Surrounding {
+                tag: element.tag,
+                span: ast::SourceSpan::covering_inlines(&element.contents),
+                contents: element.contents,
+            });
+        }
+    }
+
+    /// Ends the current sentence, unless it is still empty.
+    fn split(&mut self) {
+        for idx in (0..self.open_elements.len()).rev() {
+            let element = &mut self.open_elements[idx];
+            element.is_split = true;
+            if element.contents.is_empty() {
+                continue;
+            }
+            let mut contents = mem::take(&mut element.contents);
+            let trailing_whitespace = take_trailing_whitespace(&mut contents);
+            let node = ast::InlineNode::Surrounding {
+                tag: element.tag.clone(),
+                span: ast::SourceSpan::covering_inlines(&contents),
+                contents,
+            };
+            let parent_contents = match idx.checked_sub(1) {
+                Some(parent) => &mut self.open_elements[parent].contents,
+                None => &mut self.current,
+            };
+            parent_contents.push(node);
+            parent_contents.extend(trailing_whitespace);
+        }
+        if !self.current.is_empty() {
+            self.sentences
+                .push(wrap_sentence(mem::take(&mut self.current)));
+        }
+    }
+
+    fn finish(mut self) -> ast::InlineNodeList<'a> {
+        debug_assert!(self.open_elements.is_empty());
+        self.split();
+        self.sentences
     }
 }
 
-fn is_split_point(pos: usize, proposed_split_positions: &[usize]) -> bool {
-    proposed_split_positions.iter().any(|x| *x == pos)
+/// Walks the nodes of a leaf once, along with its offset map and the sentence
+/// boundaries found in its text, feeding them to a `SentenceBuilder`.
+struct Segmenter<'m, 'o> {
+    map: &'m [OffsetEntry],
+    next_entry: usize,
+    offsets: &'o [usize],
 }
 
-fn regroup_inlines<'a>(
-    input: ast::InlineNodeList<'a>,
-    output: &mut ast::InlineNodeList<'a>,
-    wrapper_fn: impl Fn(ast::InlineNodeList<'a>) -> ast::InlineNode,
-    nowrap_check_fn: impl Fn(&ast::InlineNode<'a>) -> bool,
-    proposed_split_positions: &[usize],
-    mut accumulative_length: usize,
-) {
-    use crate::textualize::textualize_inline_node;
-    let mut intermediate_list = Vec::new();
-    let mut intermediate_length = 0;
-    for node in input {
-        let mut textualize_str = String::new();
-        textualize_inline_node(&node, &mut textualize_str);
-        if textualize_str.is_empty() {
-            intermediate_list.push(node);
-            continue;
+impl<'m, 'o> Segmenter<'m, 'o> {
+    fn next_offset(&self) -> Option<usize> {
+        self.offsets.first().copied()
+    }
+
+    fn skip_offset(&mut self) {
+        self.offsets = &self.offsets[1..];
+    }
+
+    fn segment_node<'a>(&mut self, node: ast::InlineNode<'a>, builder: &mut SentenceBuilder<'a>) {
+        let entry = &self.map[self.next_entry];
+        let range = entry.range.clone();
+        let subtree_end = entry.subtree_end;
+        self.next_entry += 1;
+        if range.is_empty() {
+            builder.push(node);
+            self.next_entry = subtree_end;
+            return;
+        }
+        while self
+            .next_offset()
+            .is_some_and(|offset| offset <= range.start)
+        {
+            builder.split();
+            self.skip_offset();
+        }
+        if self.next_offset().is_none_or(|offset| offset >= range.end) {
+            builder.push(node);
+            self.next_entry = subtree_end;
+            return;
         }
-        if is_split_point(
-            accumulative_length + intermediate_length,
-            proposed_split_positions,
-        ) {
-            assert!(!intermediate_list.is_empty());
-            let node = if intermediate_list.len() == 1 {
-                if nowrap_check_fn(intermediate_list.first().unwrap()) {
-                    intermediate_list.into_iter().next().unwrap()
-                } else {
-                    wrapper_fn(intermediate_list)
+        match node {
+            ast::InlineNode::Content {
+                tag: ast::ContentInlineTag::Text(s),
+                span,
+            } => self.split_text(&s, &span, range, builder),
+            ast::InlineNode::Surrounding {
+                tag,
+                contents,
+                span,
+            } if is_splittable_tag(&tag) => {
+                builder.open(tag);
+                for child in contents {
+                    self.segment_node(child, builder);
                 }
-            } else {
-                wrapper_fn(intermediate_list)
-            };
-            output.push(node);
-            accumulative_length += intermediate_length;
-            intermediate_list = Vec::new();
-            intermediate_length = 0;
+                builder.close(span);
+            }
+            node => {
+                // no sentence boundary within atomic nodes
+                while self.next_offset().is_some_and(|offset| offset < range.end) {
+                    self.skip_offset();
+                }
+                builder.push(node);
+                self.next_entry = subtree_end;
+            }
         }
-        intermediate_list.push(node);
-        intermediate_length += textualize_str.len();
     }
-    if !intermediate_list.is_empty() {
-        let node = if intermediate_list.len() == 1 {
-            if nowrap_check_fn(intermediate_list.first().unwrap()) {
-                intermediate_list.into_iter().next().unwrap()
-            } else {
-                wrapper_fn(intermediate_list)
-            }
-        } else {
-            wrapper_fn(intermediate_list)
-        };
-        output.push(node);
+
+    fn split_text<'a>(
+        &mut self,
+        input: &pulldown_cmark::CowStr<'a>,
+        span: &ast::SourceSpan,
+        range: Range<usize>,
+        builder: &mut SentenceBuilder<'a>,
+    ) {
+        let mut start_pos = 0;
+        while let Some(offset) = self.next_offset().filter(|offset| *offset < range.end) {
+            let split_pos = offset - range.start;
+            builder.push(ast::InlineNode::Content {
+                tag: ast::ContentInlineTag::Text(cowstr_substr(input, start_pos..split_pos)),
+                span: text_subspan(input, span, start_pos..split_pos),
+            });
+            builder.split();
+            self.skip_offset();
+            start_pos = split_pos;
+        }
+        let total_len = input.len();
+        builder.push(ast::InlineNode::Content {
+            tag: ast::ContentInlineTag::Text(cowstr_substr(input, start_pos..total_len)),
+            span: text_subspan(input, span, start_pos..total_len),
+        });
     }
 }
 
 fn perform_sentence_segment_for_leaf_contents<'a>(inlines: &mut ast::InlineNodeList<'a>) {
-    use crate::textualize::textualize_inline_list;
     use unicode_segmentation::UnicodeSegmentation;
-    let mut textualize_result = String::new();
-    textualize_inline_list(inlines, &mut textualize_result);
-    let offsets: Vec<_> = textualize_result
+    let mut text = String::new();
+    let mut map = Vec::new();
+    map_offsets(inlines, &mut text, &mut map);
+    let offsets: Vec<_> = text
         .unicode_sentences()
         .skip(1)
-        .map(|sentence_str| sentence_str.as_ptr() as usize - textualize_result.as_ptr() as usize)
+        .map(|sentence_str| sentence_str.as_ptr() as usize - text.as_ptr() as usize)
         .collect();
-    split_inlines(inlines, &mut &offsets[..], &mut 0);
-    let regroup_input = mem::take(inlines);
-    regroup_inlines(
-        regroup_input,
-        inlines,
-        |nodes| ast::InlineNode::Surrounding {
-            tag: ast::SurroundingInlineTag::Custom(SENTENCE_TAG),
-            contents: nodes,
-        },
-        |node| {
-            if let ast::InlineNode::Surrounding {
-                tag: current_tag, ..
-            } = node
+    let mut segmenter = Segmenter {
+        map: &map,
+        next_entry: 0,
+        offsets: &offsets,
+    };
+    let mut builder = SentenceBuilder::default();
+    for node in mem::take(inlines) {
+        segmenter.segment_node(node, &mut builder);
+    }
+    // sentences made of untranslatable content only are no sentences at all
+    for node in builder.finish() {
+        match node {
+            ast::InlineNode::Surrounding { tag, contents, .. }
+                if tag == ast::SurroundingInlineTag::Custom(SENTENCE_TAG)
+                    && contents.iter().all(is_untranslatable_or_blank) =>
             {
-                *current_tag == ast::SurroundingInlineTag::Custom(SENTENCE_TAG)
-            } else {
-                false
+                inlines.extend(contents);
             }
-        },
-        &offsets[..],
-        0,
-    );
+            node => inlines.push(node),
+        }
+    }
 }
 
-fn perform_sentence_segment_for_block_node<'a>(block: &mut ast::BlockNode<'a>) {
-    match block {
-        ast::BlockNode::Container { children, .. } => {
-            for block in children.iter_mut() {
-                perform_sentence_segment_for_block_node(block);
-            }
-        }
-        ast::BlockNode::Leaf { contents, .. } => {
-            perform_sentence_segment_for_leaf_contents(contents);
-        }
-        ast::BlockNode::Markup { .. } => {
-            // do nothing
+fn is_untranslatable_or_blank(node: &ast::InlineNode<'_>) -> bool {
+    match node {
+        ast::InlineNode::Content {
+            tag: ast::ContentInlineTag::Text(s),
+            ..
+        } => s.trim().is_empty(),
+        ast::InlineNode::Content {
+            tag: ast::ContentInlineTag::SoftBreak,
+            ..
         }
+        | ast::InlineNode::Content {
+            tag: ast::ContentInlineTag::HardBreak,
+            ..
+        } => true,
+        node => crate::notranslate::is_untranslatable_inline(node),
     }
 }
 
-fn perform_sentence_segment_for_out_of_band_node<'a>(oob: &mut ast::OutOfBandNode<'a>) {
-    match oob {
-        ast::OutOfBandNode::OutOfBandContainer { children, .. } => {
-            for block in children.iter_mut() {
-                perform_sentence_segment_for_block_node(block);
+/// Segments every leaf of a document, apart from the untranslatable ones.
+struct SentenceSegmenter;
+
+impl<'a> VisitorMut<'a> for SentenceSegmenter {
+    fn visit_block_mut(&mut self, block: &mut ast::BlockNode<'a>) {
+        if crate::notranslate::is_untranslatable_block(block)
+            || crate::notes::is_comment_block(block)
+        {
+            return;
+        }
+        match block {
+            ast::BlockNode::Leaf { contents, .. } => {
+                perform_sentence_segment_for_leaf_contents(contents);
             }
+            block => walk_block_mut(self, block),
         }
-        ast::OutOfBandNode::OutOfBandLeaf { contents, .. } => {
-            perform_sentence_segment_for_leaf_contents(contents);
+    }
+
+    fn visit_outofband_mut(&mut self, oob: &mut ast::OutOfBandNode<'a>) {
+        match oob {
+            ast::OutOfBandNode::OutOfBandLeaf { contents, .. } => {
+                perform_sentence_segment_for_leaf_contents(contents);
+            }
+            oob => walk_outofband_mut(self, oob),
         }
     }
 }
 
 impl<'a> ast::Document<'a> {
+    /// Wraps each sentence in a `SENTENCE_TAG_NAME` node, leaving alone the content marked
+    /// as not to be translated (see `mark_untranslatable`).
     pub fn perform_sentence_segment(&mut self) {
-        for block in self.blocks.iter_mut() {
-            perform_sentence_segment_for_block_node(block);
-        }
+        self.mark_untranslatable();
+        self.accept_mut(&mut SentenceSegmenter);
+    }
+}
 
-        for outofband in self.outofbands.iter_mut() {
-            perform_sentence_segment_for_out_of_band_node(outofband);
-        }
+#[cfg(test)]
+mod tests {
+    use crate::test_support::{clause_list, segmented, spanned};
+
+    fn sentences(text: &str) -> Vec<String> {
+        clause_list(text)
+            .clauses
+            .iter()
+            .map(|clause| clause.source_text())
+            .collect()
+    }
+
+    /// The source text of each sentence of `text`.
+    fn sentence_sources(text: &str) -> Vec<&str> {
+        clause_list(text)
+            .clauses
+            .iter()
+            .map(|clause| spanned(text, clause.span()))
+            .collect()
+    }
+
+    fn render(text: &str) -> String {
+        let mut output = String::new();
+        pulldown_cmark_to_cmark::cmark(segmented(text).into_tokens(), &mut output, None).unwrap();
+        output
+    }
+
+    #[test]
+    fn text_after_inline_elements_is_split() {
+        assert_eq!(
+            sentences("Run `ls` first. Then *look* around. Done.\n"),
+            ["Run (code) first.", "Then (look) around.", "Done."]
+        );
+    }
+
+    #[test]
+    fn emphasis_split_between_sentences_is_reopened() {
+        let text = "*Hello world. N**ext one.*\n";
+        assert_eq!(sentences(text), ["(Hello world.)", "(N**ext one.)"]);
+        assert_eq!(sentence_sources(text), ["Hello world. ", "N**ext one."]);
+    }
+
+    #[test]
+    fn whitespace_ending_a_sentence_is_moved_out_of_elements() {
+        assert_eq!(render("*One. Two.*\n"), "*One.* *Two.*");
+        assert_eq!(
+            render("- **Bold start. Bold end.** tail.\n"),
+            "* **Bold start.** **Bold end.** tail."
+        );
+    }
+
+    #[test]
+    fn soft_breaks_do_not_end_sentences() {
+        let text = "One\ntwo. Three.\n";
+        assert_eq!(sentences(text), ["One two.", "Three."]);
+        assert_eq!(sentence_sources(text), ["One\ntwo. ", "Three."]);
+    }
+
+    #[test]
+    fn spans_cover_escapes_and_entities() {
+        let text = "A \\*b\\* &amp; c. D &lt; e.\n";
+        assert_eq!(sentences(text), ["A *b* & c.", "D < e."]);
+        assert_eq!(sentence_sources(text), ["A \\*b\\* &amp; c. ", "D &lt; e."]);
     }
 }
@@ -0,0 +1,233 @@
+//! Serialization of the `pulldown_cmark` types used in the AST, which implement neither
+//! `Serialize` nor `Deserialize`, for use with `#[serde(with = "...")]`.
+//!
+//! Strings are always deserialized into owned `CowStr`s.
+use crate::ast::{Alignment, CodeBlockKind, InlineNodeList, LinkType};
+use crate::clause::ClauseContents;
+use pulldown_cmark::CowStr;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::sync::Arc;
+
+pub(crate) mod cowstr {
+    use super::*;
+
+    pub(crate) fn serialize<S: Serializer>(
+        s: &CowStr<'_>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(s)
+    }
+
+    pub(crate) fn deserialize<'de, 'a, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<CowStr<'a>, D::Error> {
+        String::deserialize(deserializer).map(CowStr::from)
+    }
+}
+
+pub(crate) mod cowstr_option {
+    use super::*;
+
+    pub(crate) fn serialize<S: Serializer>(
+        s: &Option<CowStr<'_>>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        s.as_deref().serialize(serializer)
+    }
+
+    pub(crate) fn deserialize<'de, 'a, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Option<CowStr<'a>>, D::Error> {
+        Ok(Option::<String>::deserialize(deserializer)?.map(CowStr::from))
+    }
+}
+
+pub(crate) mod cowstr_vec {
+    use super::*;
+
+    pub(crate) fn serialize<S: Serializer>(
+        list: &[CowStr<'_>],
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(list.iter().map(|s| s.as_ref()))
+    }
+
+    pub(crate) fn deserialize<'de, 'a, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Vec<CowStr<'a>>, D::Error> {
+        Ok(Vec::<String>::deserialize(deserializer)?
+            .into_iter()
+            .map(CowStr::from)
+            .collect())
+    }
+}
+
+/// The translations of a clause, as a list of language and contents pairs.
+pub(crate) mod translations {
+    use super::*;
+
+    pub(crate) fn serialize<S: Serializer>(
+        list: &[(CowStr<'_>, InlineNodeList<'_>)],
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(
+            list.iter()
+                .map(|(language, contents)| (language.as_ref(), contents)),
+        )
+    }
+
+    pub(crate) fn deserialize<'de, 'a, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Vec<(CowStr<'a>, InlineNodeList<'a>)>, D::Error> {
+        Ok(
+            Vec::<(String, InlineNodeList<'a>)>::deserialize(deserializer)?
+                .into_iter()
+                .map(|(language, contents)| (language.into(), contents))
+                .collect(),
+        )
+    }
+}
+
+/// The source of a clause, always deserialized into owned contents.
+pub(crate) mod clause_contents {
+    use super::*;
+
+    pub(crate) fn serialize<S: Serializer>(
+        contents: &ClauseContents<'_>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(contents.iter())
+    }
+
+    pub(crate) fn deserialize<'de, 'a, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<ClauseContents<'a>, D::Error> {
+        InlineNodeList::deserialize(deserializer).map(ClauseContents::Owned)
+    }
+}
+
+/// The headings enclosing a clause, shared by the clauses under them.
+pub(crate) mod heading_path {
+    use super::*;
+
+    pub(crate) fn serialize<S: Serializer>(
+        path: &Arc<[String]>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        path[..].serialize(serializer)
+    }
+
+    pub(crate) fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Arc<[String]>, D::Error> {
+        Vec::<String>::deserialize(deserializer).map(Arc::from)
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(remote = "LinkType")]
+pub(crate) enum LinkTypeDef {
+    Inline,
+    Reference,
+    ReferenceUnknown,
+    Collapsed,
+    CollapsedUnknown,
+    Shortcut,
+    ShortcutUnknown,
+    Autolink,
+    Email,
+}
+
+// only used through `with`, so never built
+#[allow(dead_code)]
+#[derive(Serialize, Deserialize)]
+#[serde(remote = "CodeBlockKind")]
+pub(crate) enum CodeBlockKindDef<'a> {
+    Indented,
+    Fenced(#[serde(with = "cowstr")] CowStr<'a>),
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(remote = "Alignment")]
+enum AlignmentDef {
+    None,
+    Left,
+    Center,
+    Right,
+}
+
+#[derive(Serialize, Deserialize)]
+struct AlignmentWrapper(#[serde(with = "AlignmentDef")] Alignment);
+
+/// The column alignments of a table.
+pub(crate) mod alignments {
+    use super::*;
+
+    pub(crate) fn serialize<S: Serializer>(
+        list: &[Alignment],
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(list.iter().copied().map(AlignmentWrapper))
+    }
+
+    pub(crate) fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Vec<Alignment>, D::Error> {
+        Ok(Vec::<AlignmentWrapper>::deserialize(deserializer)?
+            .into_iter()
+            .map(|AlignmentWrapper(alignment)| alignment)
+            .collect())
+    }
+}
+
+#[cfg(all(test, feature = "serde"))]
+mod tests {
+    use crate::ast;
+    use crate::clause::DocumentClauseList;
+    use crate::test_support::{clause_list, translate};
+    use crate::textualize::textualize_inline_list;
+
+    #[test]
+    fn documents_round_trip_through_json() {
+        let text = "See [the docs](https://example.com) or <https://example.org>.\n\n\
+                    | a | b | c |\n|:--|:-:|--:|\n| d | e | f |\n\n\
+                    ```rust\nfn main() {}\n```\n\n    indented\n";
+        let doc =
+            ast::Document::from_markdown(text, pulldown_cmark::Options::ENABLE_TABLES).unwrap();
+        let json = serde_json::to_string(&doc).unwrap();
+        for expected in [
+            "\"Inline\"",
+            "\"Autolink\"",
+            "\"Left\"",
+            "\"Center\"",
+            "\"Right\"",
+        ] {
+            assert!(json.contains(expected), "{} not in {}", expected, json);
+        }
+        assert!(json.contains("{\"Fenced\":\"rust\"}"), "{}", json);
+        assert!(json.contains("\"Indented\""), "{}", json);
+
+        // `CowStr`s come back owned, so the documents are compared through their JSON
+        let loaded: ast::Document<'_> = serde_json::from_str(&json).unwrap();
+        assert_eq!(serde_json::to_string(&loaded).unwrap(), json);
+    }
+
+    #[test]
+    fn translated_clauses_round_trip_through_json() {
+        let mut clause_list = clause_list("Run `ls` *now*.\n");
+        translate(&mut clause_list, 1, "zh-CN", "*现在*运行 `ls`。");
+        clause_list.clauses[0]
+            .clause_fuzzy_languages
+            .push("zh-CN".into());
+
+        let json = serde_json::to_string(&clause_list).unwrap();
+        let loaded: DocumentClauseList<'_> = serde_json::from_str(&json).unwrap();
+        let clause = &loaded.clauses[0];
+        let mut text = String::new();
+        textualize_inline_list(clause.translation("zh-CN").unwrap(), &mut text);
+        assert_eq!(text, "(现在)运行 (code)。");
+        assert!(clause.is_fuzzy("zh-CN"));
+        assert_eq!(clause.span(), clause_list.clauses[0].span());
+        assert_eq!(serde_json::to_string(&loaded).unwrap(), json);
+    }
+}
@@ -0,0 +1,198 @@
+use crate::clause;
+use crate::textualize::plain_text;
+
+/// Whether `c` is written without spaces between words, so that each character counts
+/// as a word of its own: Han ideographs and kana.
+pub(crate) fn is_cjk_word_char(c: char) -> bool {
+    matches!(c as u32,
+        0x3005..=0x3007     // iteration marks and ideographic zero
+        | 0x3040..=0x30ff   // hiragana, katakana
+        | 0x31f0..=0x31ff   // katakana phonetic extensions
+        | 0x3400..=0x4dbf   // CJK extension A
+        | 0x4e00..=0x9fff   // CJK unified ideographs
+        | 0xf900..=0xfaff   // CJK compatibility ideographs
+        | 0xff66..=0xff9d   // halfwidth katakana
+        | 0x20000..=0x3134f // CJK extensions B to G
+    )
+}
+
+/// Counts the words of `text`, the way translation vendors usually do: runs of letters
+/// and digits separated by whitespace count once, while each CJK character counts as
+/// a word by itself. Punctuation on its own is not counted.
+pub fn count_words(text: &str) -> usize {
+    let mut count = 0;
+    let mut in_word = false;
+    for c in text.chars() {
+        if is_cjk_word_char(c) {
+            count += 1;
+            in_word = false;
+        } else if c.is_alphanumeric() {
+            if !in_word {
+                count += 1;
+            }
+            in_word = true;
+        } else if c.is_whitespace() {
+            in_word = false;
+        }
+    }
+    count
+}
+
+/// Counts the characters of `text`, leaving out whitespace.
+pub fn count_chars(text: &str) -> usize {
+    text.chars().filter(|c| !c.is_whitespace()).count()
+}
+
+/// How far the translation into one language has come.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct LanguageStats {
+    pub language: String,
+    pub translated: usize,
+    pub fuzzy: usize,
+    pub untranslated: usize,
+    /// Source words of the clauses still to be translated, fuzzy ones included.
+    pub remaining_words: usize,
+}
+
+impl LanguageStats {
+    fn new(language: &str) -> Self {
+        LanguageStats {
+            language: language.to_string(),
+            translated: 0,
+            fuzzy: 0,
+            untranslated: 0,
+            remaining_words: 0,
+        }
+    }
+
+    /// Percentage of clauses translated and not fuzzy, 100 when there are none.
+    pub fn percent_complete(&self) -> f64 {
+        let total = self.translated + self.fuzzy + self.untranslated;
+        if total == 0 {
+            100.0
+        } else {
+            self.translated as f64 * 100.0 / total as f64
+        }
+    }
+}
+
+/// Statistics of a clause list, or of several added together.
+#[derive(Clone, Default, PartialEq, Eq, Debug)]
+pub struct ClauseListStats {
+    pub clauses: usize,
+    pub source_words: usize,
+    pub source_chars: usize,
+    pub languages: Vec<LanguageStats>,
+}
+
+impl ClauseListStats {
+    pub fn language(&self, language: &str) -> Option<&LanguageStats> {
+        self.languages
+            .iter()
+            .find(|stats| stats.language == language)
+    }
+
+    fn language_mut(&mut self, language: &str) -> &mut LanguageStats {
+        match self
+            .languages
+            .iter()
+            .position(|stats| stats.language == language)
+        {
+            Some(pos) => &mut self.languages[pos],
+            None => {
+                self.languages.push(LanguageStats::new(language));
+                self.languages.last_mut().unwrap()
+            }
+        }
+    }
+
+    /// Adds the statistics of another clause list to these, e.g. to total a whole book.
+    pub fn add(&mut self, other: &ClauseListStats) {
+        self.clauses += other.clauses;
+        self.source_words += other.source_words;
+        self.source_chars += other.source_chars;
+        for other_language in &other.languages {
+            let language = self.language_mut(&other_language.language);
+            language.translated += other_language.translated;
+            language.fuzzy += other_language.fuzzy;
+            language.untranslated += other_language.untranslated;
+            language.remaining_words += other_language.remaining_words;
+        }
+    }
+}
+
+impl<'a> clause::DocumentClauseList<'a> {
+    /// Computes the statistics of this clause list for each of `languages`, or for every
+    /// language other than `source_language` that has a translation when it is empty.
+    ///
+    /// A translation counts as translated when it is present, not empty and not fuzzy.
+    /// Machine translations still need review, so they count as fuzzy.
+    pub fn stats(&self, source_language: &str, languages: &[&str]) -> ClauseListStats {
+        let mut stats = ClauseListStats::default();
+        for language in languages {
+            let _ = stats.language_mut(language);
+        }
+        if languages.is_empty() {
+            for clause in &self.clauses {
+                for (language, _) in clause.translations() {
+                    if language != source_language {
+                        let _ = stats.language_mut(language);
+                    }
+                }
+            }
+        }
+
+        for clause in &self.clauses {
+            let source_text = plain_text(&clause.clause_contents);
+            let words = count_words(&source_text);
+            stats.clauses += 1;
+            stats.source_words += words;
+            stats.source_chars += count_chars(&source_text);
+            for language_stats in stats.languages.iter_mut() {
+                let language = language_stats.language.as_str();
+                let is_present = clause
+                    .translation(language)
+                    .is_some_and(|contents| !contents.is_empty());
+                if !is_present {
+                    language_stats.untranslated += 1;
+                    language_stats.remaining_words += words;
+                } else if clause.is_fuzzy(language) || clause.is_machine_translated(language) {
+                    language_stats.fuzzy += 1;
+                    language_stats.remaining_words += words;
+                } else {
+                    language_stats.translated += 1;
+                }
+            }
+        }
+        stats
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::clause_list;
+
+    #[test]
+    fn words_are_counted() {
+        assert_eq!(count_words("Hello, big world!"), 3);
+        assert_eq!(count_words("你好 world"), 3);
+        assert_eq!(count_chars("a b\tc"), 3);
+    }
+
+    #[test]
+    fn link_and_emphasis_text_is_counted_but_not_code() {
+        let stats = clause_list("Read [the *whole* guide](https://example.com) or `run`.\n")
+            .stats("en-US", &["fr"]);
+        assert_eq!(stats.clauses, 1);
+        assert_eq!(stats.source_words, 5);
+        assert_eq!(stats.source_chars, "Readthewholeguideor.".len());
+        assert_eq!(stats.language("fr").unwrap().remaining_words, 5);
+    }
+
+    #[test]
+    fn raw_html_is_not_counted() {
+        let stats = clause_list("Press <kbd>Enter</kbd> now.\n").stats("en-US", &[]);
+        assert_eq!(stats.source_words, 3);
+    }
+}
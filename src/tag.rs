@@ -196,6 +196,59 @@ pub(crate) fn unconvert_tag(t: ConvertedTag<'_>) -> UnconvertedTag<'_> {
     }
 }
 
+pub(crate) fn cowstr_into_static(s: pulldown_cmark::CowStr<'_>) -> pulldown_cmark::CowStr<'static> {
+    match s {
+        pulldown_cmark::CowStr::Borrowed(s) => pulldown_cmark::CowStr::Boxed(s.into()),
+        pulldown_cmark::CowStr::Boxed(s) => pulldown_cmark::CowStr::Boxed(s),
+        pulldown_cmark::CowStr::Inlined(s) => pulldown_cmark::CowStr::Inlined(s),
+    }
+}
+
+pub(crate) fn tag_into_static(tag: pulldown_cmark::Tag<'_>) -> pulldown_cmark::Tag<'static> {
+    use pulldown_cmark::{CodeBlockKind, Tag};
+
+    match tag {
+        Tag::Paragraph => Tag::Paragraph,
+        Tag::Heading(s) => Tag::Heading(s),
+        Tag::BlockQuote => Tag::BlockQuote,
+        Tag::CodeBlock(CodeBlockKind::Indented) => Tag::CodeBlock(CodeBlockKind::Indented),
+        Tag::CodeBlock(CodeBlockKind::Fenced(s)) => {
+            Tag::CodeBlock(CodeBlockKind::Fenced(cowstr_into_static(s)))
+        }
+        Tag::List(s) => Tag::List(s),
+        Tag::Item => Tag::Item,
+        Tag::FootnoteDefinition(s) => Tag::FootnoteDefinition(cowstr_into_static(s)),
+        Tag::Table(s) => Tag::Table(s),
+        Tag::TableHead => Tag::TableHead,
+        Tag::TableRow => Tag::TableRow,
+        Tag::TableCell => Tag::TableCell,
+        Tag::Emphasis => Tag::Emphasis,
+        Tag::Strong => Tag::Strong,
+        Tag::Strikethrough => Tag::Strikethrough,
+        Tag::Link(a, b, c) => Tag::Link(a, cowstr_into_static(b), cowstr_into_static(c)),
+        Tag::Image(a, b, c) => Tag::Image(a, cowstr_into_static(b), cowstr_into_static(c)),
+    }
+}
+
+pub(crate) fn event_into_static(
+    event: pulldown_cmark::Event<'_>,
+) -> pulldown_cmark::Event<'static> {
+    use pulldown_cmark::Event;
+
+    match event {
+        Event::Start(tag) => Event::Start(tag_into_static(tag)),
+        Event::End(tag) => Event::End(tag_into_static(tag)),
+        Event::Text(s) => Event::Text(cowstr_into_static(s)),
+        Event::Code(s) => Event::Code(cowstr_into_static(s)),
+        Event::Html(s) => Event::Html(cowstr_into_static(s)),
+        Event::FootnoteReference(s) => Event::FootnoteReference(cowstr_into_static(s)),
+        Event::SoftBreak => Event::SoftBreak,
+        Event::HardBreak => Event::HardBreak,
+        Event::Rule => Event::Rule,
+        Event::TaskListMarker(s) => Event::TaskListMarker(s),
+    }
+}
+
 pub(crate) fn is_token_inline<'a>(e: &pulldown_cmark::Event<'a>) -> bool {
     match e {
         pulldown_cmark::Event::Start(tag) | pulldown_cmark::Event::End(tag) => match tag {
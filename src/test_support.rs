@@ -0,0 +1,56 @@
+//! Fixtures shared by the unit tests.
+use crate::ast;
+use crate::clause;
+
+/// Parses `text`, which must be valid CommonMark.
+pub(crate) fn parse(text: &str) -> ast::Document<'_> {
+    crate::from_tokens::cmark_ast_from_tokens(pulldown_cmark::Parser::new(text), text).unwrap()
+}
+
+/// Parses and segments `text`.
+pub(crate) fn segmented(text: &str) -> ast::Document<'_> {
+    let mut doc = parse(text);
+    doc.perform_sentence_segment();
+    doc
+}
+
+/// Parses and segments `text`, and extracts its clauses in `en-US`.
+pub(crate) fn clause_list(text: &str) -> clause::DocumentClauseList<'static> {
+    segmented(text)
+        .extract_clause_list(&"en-US".into())
+        .into_owned()
+}
+
+/// The part of `text` covered by `span`.
+pub(crate) fn spanned<'t>(text: &'t str, span: &ast::SourceSpan) -> &'t str {
+    &text[span.range.clone()]
+}
+
+/// The contents of the first paragraph of `text`.
+pub(crate) fn inlines(text: &str) -> ast::InlineNodeList<'_> {
+    match parse(text).blocks.into_iter().next() {
+        Some(ast::BlockNode::Leaf { contents, .. }) => contents,
+        _ => panic!("not a paragraph"),
+    }
+}
+
+/// Translates clause `id` of `clause_list` into `language`.
+pub(crate) fn translate<'a>(
+    clause_list: &mut clause::DocumentClauseList<'a>,
+    id: usize,
+    language: &str,
+    text: &'a str,
+) {
+    clause_list
+        .get_mut(id)
+        .unwrap()
+        .set_translation(language, inlines(text));
+}
+
+/// A fresh directory under the system temporary directory.
+pub(crate) fn temp_dir(name: &str) -> std::path::PathBuf {
+    let dir = std::env::temp_dir().join(format!("mdtranslation-{}-{}", name, std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
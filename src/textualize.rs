@@ -1,58 +1,106 @@
 use crate::ast;
+use crate::visit::{walk_inline, Visitor};
 
-pub fn textualize_inline_list(inlines: &ast::InlineNodeList<'_>, textualize_result: &mut String) {
+/// Writes inlines as text, with a placeholder standing for each element.
+struct Textualizer<'o> {
+    output: &'o mut String,
+}
+
+impl<'d, 'a> Visitor<'d, 'a> for Textualizer<'_> {
+    fn visit_inline(&mut self, node: &'d ast::InlineNode<'a>) {
+        match node {
+            ast::InlineNode::Surrounding { tag, .. } => match tag {
+                ast::SurroundingInlineTag::Emphasis
+                | ast::SurroundingInlineTag::Strong
+                | ast::SurroundingInlineTag::Strikethrough => {
+                    *self.output += "(";
+                    walk_inline(self, node);
+                    *self.output += ")";
+                }
+                ast::SurroundingInlineTag::Link(_, _, _) => {
+                    *self.output += "(link)";
+                }
+                ast::SurroundingInlineTag::Image(_, _, _) => {
+                    *self.output += "(image)";
+                }
+                ast::SurroundingInlineTag::Custom(_) => {
+                    walk_inline(self, node);
+                }
+            },
+            ast::InlineNode::Content { tag, .. } => match tag {
+                ast::ContentInlineTag::Text(s) => {
+                    *self.output += s.as_ref();
+                }
+                ast::ContentInlineTag::Code(_) => {
+                    *self.output += "(code)";
+                }
+                ast::ContentInlineTag::RawHtml(_) => {
+                    *self.output += "(raw html)";
+                }
+                ast::ContentInlineTag::FootnoteRef(_) => {
+                    *self.output += "(ref)";
+                }
+                ast::ContentInlineTag::TaskListMarker(_) => {
+                    *self.output += "(marker)";
+                }
+                ast::ContentInlineTag::SoftBreak => {
+                    *self.output += " ";
+                }
+                ast::ContentInlineTag::HardBreak => {
+                    *self.output += "\n";
+                }
+                ast::ContentInlineTag::Custom(s) => {
+                    *self.output += "(";
+                    *self.output += s.as_ref();
+                    *self.output += ")";
+                }
+            },
+        }
+    }
+}
+
+pub fn textualize_inline_list(inlines: &[ast::InlineNode<'_>], textualize_result: &mut String) {
+    let mut textualizer = Textualizer {
+        output: textualize_result,
+    };
     for inline in inlines {
-        textualize_inline_node(inline, textualize_result);
+        textualizer.visit_inline(inline);
     }
 }
 
 pub fn textualize_inline_node(node: &ast::InlineNode<'_>, textualize_result: &mut String) {
-    match node {
-        ast::InlineNode::Surrounding { tag, contents } => match tag {
-            ast::SurroundingInlineTag::Emphasis
-            | ast::SurroundingInlineTag::Strong
-            | ast::SurroundingInlineTag::Strikethrough => {
-                *textualize_result += "(";
-                textualize_inline_list(contents, textualize_result);
-                *textualize_result += ")";
-            }
-            ast::SurroundingInlineTag::Link(_, _, _) => {
-                *textualize_result += "(link)";
-            }
-            ast::SurroundingInlineTag::Image(_, _, _) => {
-                *textualize_result += "(image)";
-            }
-            ast::SurroundingInlineTag::Custom(_) => {
-                textualize_inline_list(contents, textualize_result);
-            }
-        },
-        ast::InlineNode::Content { tag } => match tag {
-            ast::ContentInlineTag::Text(s) => {
-                *textualize_result += s.as_ref();
-            }
-            ast::ContentInlineTag::Code(_) => {
-                *textualize_result += "(code)";
-            }
-            ast::ContentInlineTag::RawHtml(_) => {
-                *textualize_result += "(raw html)";
-            }
-            ast::ContentInlineTag::FootnoteRef(_) => {
-                *textualize_result += "(ref)";
-            }
-            ast::ContentInlineTag::TaskListMarker(_) => {
-                *textualize_result += "(marker)";
-            }
-            ast::ContentInlineTag::SoftBreak => {
-                *textualize_result += " ";
-            }
-            ast::ContentInlineTag::HardBreak => {
-                *textualize_result += "\n";
-            }
-            ast::ContentInlineTag::Custom(s) => {
-                *textualize_result += "(";
-                *textualize_result += s.as_ref();
-                *textualize_result += ")";
-            }
-        },
+    Textualizer {
+        output: textualize_result,
+    }
+    .visit_inline(node);
+}
+
+/// Collects the text a reader sees, see `plain_text`.
+#[derive(Default)]
+struct PlainText {
+    output: String,
+}
+
+impl<'d, 'a> Visitor<'d, 'a> for PlainText {
+    fn visit_inline(&mut self, node: &'d ast::InlineNode<'a>) {
+        match node {
+            ast::InlineNode::Surrounding { .. } => walk_inline(self, node),
+            ast::InlineNode::Content { tag, .. } => match tag {
+                ast::ContentInlineTag::Text(s) => self.output += s,
+                ast::ContentInlineTag::SoftBreak => self.output += " ",
+                ast::ContentInlineTag::HardBreak => self.output += "\n",
+                _ => {}
+            },
+        }
+    }
+}
+
+/// The text a reader sees in `inlines`, link and emphasis text included, leaving out
+/// code and HTML.
+pub fn plain_text(inlines: &[ast::InlineNode<'_>]) -> String {
+    let mut plain_text = PlainText::default();
+    for inline in inlines {
+        plain_text.visit_inline(inline);
     }
+    plain_text.output
 }
@@ -0,0 +1,531 @@
+//! Machine translation backends, used to pre-fill untranslated clauses.
+//!
+//! Clauses are handed to a backend as plain text, with their markup protected by
+//! placeholders: `⟦1⟧` for an element without text of its own, such as inline code, and
+//! `⟦2⟧…⟦/2⟧` around the text of an element such as emphasis or a link. Backends are
+//! expected to keep the placeholders in their output, in any order. A bracket that is
+//! part of the source text becomes a `⟦n⟧` placeholder too.
+use crate::ast;
+use crate::clause;
+use std::io::{self, Write};
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum TranslatorError {
+    #[error("io error: {0}")]
+    IO(#[from] io::Error),
+    #[error("json error: {0}")]
+    Json(#[from] serde_json::Error),
+    #[error("translator failed: {0}")]
+    Backend(String),
+    #[error("expected {expected} translations, got {got}")]
+    TranslationCount { expected: usize, got: usize },
+}
+
+/// A clause to translate, with its markup protected by placeholders.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct TranslationUnit {
+    pub id: usize,
+    pub text: String,
+    /// Where the clause lives, e.g. `list item, Installation > Linux`.
+    pub context: String,
+    pub notes: Vec<String>,
+}
+
+pub trait Translator {
+    /// Translates each of `units`, returning the target text of each in order.
+    fn translate(
+        &mut self,
+        source_language: &str,
+        target_language: &str,
+        units: &[TranslationUnit],
+    ) -> Result<Vec<String>, TranslatorError>;
+}
+
+/// Runs a local executable for each batch, writing a JSON request to its standard
+/// input and reading a JSON response from its standard output.
+///
+/// The request looks like
+/// `{"source_language": "en-US", "target_language": "zh-CN", "units": [{"id": 1,
+/// "text": "…", "context": "…", "notes": []}]}`, and the response like
+/// `{"translations": ["…"]}`, with one translation per unit, in order.
+#[derive(Clone, Debug)]
+pub struct CommandTranslator {
+    pub program: PathBuf,
+    pub args: Vec<String>,
+}
+
+impl Translator for CommandTranslator {
+    fn translate(
+        &mut self,
+        source_language: &str,
+        target_language: &str,
+        units: &[TranslationUnit],
+    ) -> Result<Vec<String>, TranslatorError> {
+        use serde_json::{json, Value};
+
+        let request = json!({
+            "source_language": source_language,
+            "target_language": target_language,
+            "units": units
+                .iter()
+                .map(|unit| json!({
+                    "id": unit.id,
+                    "text": unit.text,
+                    "context": unit.context,
+                    "notes": unit.notes,
+                }))
+                .collect::<Vec<_>>(),
+        });
+        let mut child = Command::new(&self.program)
+            .args(&self.args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()?;
+        let request_text = serde_json::to_vec(&request)?;
+        let mut stdin = child.stdin.take().expect("stdin is piped");
+        // write from another thread so that a backend answering early cannot deadlock
+        let writer = std::thread::spawn(move || stdin.write_all(&request_text));
+        let output = child.wait_with_output()?;
+        writer.join().expect("writer thread panicked")?;
+        if !output.status.success() {
+            return Err(TranslatorError::Backend(format!(
+                "{}: {}",
+                output.status,
+                String::from_utf8_lossy(&output.stderr).trim()
+            )));
+        }
+
+        let response: Value = serde_json::from_slice(&output.stdout)?;
+        let translations = response
+            .get("translations")
+            .and_then(Value::as_array)
+            .ok_or_else(|| TranslatorError::Backend("response has no translations".into()))?;
+        translations
+            .iter()
+            .map(|translation| {
+                translation
+                    .as_str()
+                    .map(str::to_string)
+                    .ok_or_else(|| TranslatorError::Backend("translation is not a string".into()))
+            })
+            .collect()
+    }
+}
+
+/// A backend for tests, which "translates" by prefixing the target language, e.g.
+/// `[zh-CN] Hello ⟦1⟧world⟦/1⟧.`
+#[derive(Clone, Copy, Default, Debug)]
+pub struct MockTranslator;
+
+impl Translator for MockTranslator {
+    fn translate(
+        &mut self,
+        _source_language: &str,
+        target_language: &str,
+        units: &[TranslationUnit],
+    ) -> Result<Vec<String>, TranslatorError> {
+        Ok(units
+            .iter()
+            .map(|unit| format!("[{}] {}", target_language, unit.text))
+            .collect())
+    }
+}
+
+const PLACEHOLDER_OPEN: char = '⟦';
+const PLACEHOLDER_CLOSE: char = '⟧';
+
+/// A piece of markup taken out of a clause.
+#[derive(Clone, Debug)]
+pub enum Placeholder<'a> {
+    /// An element replaced as a whole, written `⟦n⟧`.
+    Element(ast::InlineNode<'a>),
+    /// An element whose text is translated, written `⟦n⟧…⟦/n⟧`.
+    Paired {
+        tag: ast::SurroundingInlineTag<'a>,
+        span: ast::SourceSpan,
+    },
+}
+
+/// The markup taken out of a clause, indexed by placeholder number minus one.
+pub type Placeholders<'a> = Vec<Placeholder<'a>>;
+
+fn push_element<'a>(
+    node: ast::InlineNode<'a>,
+    text: &mut String,
+    placeholders: &mut Placeholders<'a>,
+) {
+    placeholders.push(Placeholder::Element(node));
+    *text += &format!(
+        "{}{}{}",
+        PLACEHOLDER_OPEN,
+        placeholders.len(),
+        PLACEHOLDER_CLOSE
+    );
+}
+
+/// Adds `s` to `text`, turning each placeholder bracket in it into a placeholder of its
+/// own, so that the brackets of the source cannot be mistaken for placeholders.
+fn protect_text<'a>(
+    s: &str,
+    span: &ast::SourceSpan,
+    text: &mut String,
+    placeholders: &mut Placeholders<'a>,
+) {
+    let mut rest = s;
+    while let Some(pos) = rest.find([PLACEHOLDER_OPEN, PLACEHOLDER_CLOSE]) {
+        *text += &rest[..pos];
+        let bracket_len = PLACEHOLDER_OPEN.len_utf8();
+        let bracket = ast::InlineNode::Content {
+            tag: ast::ContentInlineTag::Text(rest[pos..pos + bracket_len].to_string().into()),
+            span: span.clone(),
+        };
+        push_element(bracket, text, placeholders);
+        rest = &rest[pos + bracket_len..];
+    }
+    *text += rest;
+}
+
+fn protect_inlines<'a>(
+    inlines: &[ast::InlineNode<'a>],
+    text: &mut String,
+    placeholders: &mut Placeholders<'a>,
+) {
+    for node in inlines {
+        match node {
+            ast::InlineNode::Content {
+                tag: ast::ContentInlineTag::Text(s),
+                span,
+            } => protect_text(s, span, text, placeholders),
+            ast::InlineNode::Content {
+                tag: ast::ContentInlineTag::SoftBreak,
+                ..
+            } => *text += " ",
+            ast::InlineNode::Surrounding {
+                tag: tag @ ast::SurroundingInlineTag::Emphasis,
+                contents,
+                span,
+            }
+            | ast::InlineNode::Surrounding {
+                tag: tag @ ast::SurroundingInlineTag::Strong,
+                contents,
+                span,
+            }
+            | ast::InlineNode::Surrounding {
+                tag: tag @ ast::SurroundingInlineTag::Strikethrough,
+                contents,
+                span,
+            }
+            | ast::InlineNode::Surrounding {
+                tag: tag @ ast::SurroundingInlineTag::Link(..),
+                contents,
+                span,
+            } => {
+                placeholders.push(Placeholder::Paired {
+                    tag: tag.clone(),
+                    span: span.clone(),
+                });
+                let number = placeholders.len();
+                *text += &format!("{}{}{}", PLACEHOLDER_OPEN, number, PLACEHOLDER_CLOSE);
+                protect_inlines(contents, text, placeholders);
+                *text += &format!("{}/{}{}", PLACEHOLDER_OPEN, number, PLACEHOLDER_CLOSE);
+            }
+            node => {
+                // everything else, untranslatable content included, is kept as is
+                push_element(node.clone(), text, placeholders);
+            }
+        }
+    }
+}
+
+/// Turns a clause into text for a translator, returning the markup it took out.
+pub fn protect_placeholders<'a>(inlines: &[ast::InlineNode<'a>]) -> (String, Placeholders<'a>) {
+    let mut text = String::new();
+    let mut placeholders = Vec::new();
+    protect_inlines(inlines, &mut text, &mut placeholders);
+    (text, placeholders)
+}
+
+fn push_text<'a>(stack: &mut [(Option<usize>, ast::InlineNodeList<'a>)], text: &str) {
+    if text.is_empty() {
+        return;
+    }
+    let (_, contents) = stack.last_mut().expect("stack is never empty");
+    contents.push(ast::InlineNode::Content {
+        tag: ast::ContentInlineTag::Text(text.to_string().into()),
+        span: Default::default(),
+    });
+}
+
+/// Closes the element opened by placeholder `number` and everything opened inside it.
+fn close_element<'a>(
+    stack: &mut Vec<(Option<usize>, ast::InlineNodeList<'a>)>,
+    placeholders: &Placeholders<'a>,
+    number: usize,
+) {
+    if !stack.iter().any(|(open, _)| *open == Some(number)) {
+        return;
+    }
+    while let Some((Some(open), contents)) = stack.pop() {
+        if let Placeholder::Paired { tag, span } = &placeholders[open - 1] {
+            let element = ast::InlineNode::Surrounding {
+                tag: tag.clone(),
+                contents,
+                span: span.clone(),
+            };
+            stack.last_mut().unwrap().1.push(element);
+        }
+        if open == number {
+            break;
+        }
+    }
+}
+
+/// Rebuilds a clause from translated text, putting the markup back in place of the
+/// placeholders. Unknown placeholders are kept as text and unclosed elements are closed
+/// at the end.
+pub fn restore_placeholders<'a>(
+    text: &str,
+    placeholders: &Placeholders<'a>,
+) -> ast::InlineNodeList<'a> {
+    let mut stack: Vec<(Option<usize>, ast::InlineNodeList<'a>)> = vec![(None, Vec::new())];
+    let mut rest = text;
+    while let Some(start) = rest.find(PLACEHOLDER_OPEN) {
+        let inner_start = start + PLACEHOLDER_OPEN.len_utf8();
+        let placeholder = rest[inner_start..].find(PLACEHOLDER_CLOSE).and_then(|len| {
+            let inner = &rest[inner_start..inner_start + len];
+            let (is_closing, number) = match inner.strip_prefix('/') {
+                Some(number) => (true, number),
+                None => (false, inner),
+            };
+            let number: usize = number.parse().ok()?;
+            if number == 0 || number > placeholders.len() {
+                return None;
+            }
+            Some((
+                is_closing,
+                number,
+                inner_start + len + PLACEHOLDER_CLOSE.len_utf8(),
+            ))
+        });
+        let (is_closing, number, end) = match placeholder {
+            Some(placeholder) => placeholder,
+            None => {
+                push_text(&mut stack, &rest[..inner_start]);
+                rest = &rest[inner_start..];
+                continue;
+            }
+        };
+        push_text(&mut stack, &rest[..start]);
+        rest = &rest[end..];
+        match &placeholders[number - 1] {
+            Placeholder::Paired { .. } if is_closing => {
+                close_element(&mut stack, placeholders, number);
+            }
+            Placeholder::Paired { .. } => stack.push((Some(number), Vec::new())),
+            Placeholder::Element(node) if !is_closing => {
+                stack.last_mut().unwrap().1.push(node.clone())
+            }
+            Placeholder::Element(_) => {}
+        }
+    }
+    push_text(&mut stack, rest);
+    while stack.len() > 1 {
+        let open = stack.last().unwrap().0.unwrap();
+        close_element(&mut stack, placeholders, open);
+    }
+    stack.pop().unwrap().1
+}
+
+impl<'a> clause::DocumentClauseList<'a> {
+    /// Translates every clause that has no translation into `target_language` with
+    /// `translator`, `batch_size` clauses at a time, marking the results as machine
+    /// translated. Returns the number of clauses translated.
+    ///
+    /// Nothing is changed unless every batch succeeds.
+    pub fn prefill_translations(
+        &mut self,
+        translator: &mut dyn Translator,
+        source_language: &str,
+        target_language: &str,
+        batch_size: usize,
+    ) -> Result<usize, TranslatorError> {
+        let pending: Vec<usize> = self
+            .clauses
+            .iter()
+            .enumerate()
+            .filter(|(_, clause)| {
+                clause
+                    .translation(target_language)
+                    .is_none_or(|contents| contents.is_empty())
+            })
+            .map(|(pos, _)| pos)
+            .collect();
+
+        let mut translated = Vec::with_capacity(pending.len());
+        for batch in pending.chunks(batch_size.max(1)) {
+            let mut units = Vec::new();
+            let mut batch_placeholders = Vec::new();
+            for &pos in batch {
+                let clause = &self.clauses[pos];
+                let (text, placeholders) = protect_placeholders(&clause.clause_contents);
+                let mut context = clause.clause_kind.name().to_string();
+                if !clause.clause_heading_path.is_empty() {
+                    context += ", ";
+                    context += &clause.clause_heading_path.join(" > ");
+                }
+                units.push(TranslationUnit {
+                    id: clause.clause_idx,
+                    text: text.trim().to_string(),
+                    context,
+                    notes: clause.clause_notes.clone(),
+                });
+                batch_placeholders.push(placeholders);
+            }
+            let translations = translator.translate(source_language, target_language, &units)?;
+            if translations.len() != units.len() {
+                return Err(TranslatorError::TranslationCount {
+                    expected: units.len(),
+                    got: translations.len(),
+                });
+            }
+            for ((&pos, translation), placeholders) in
+                batch.iter().zip(translations).zip(batch_placeholders)
+            {
+                translated.push((pos, restore_placeholders(&translation, &placeholders)));
+            }
+        }
+
+        for (pos, contents) in translated {
+            let clause = &mut self.clauses[pos];
+            clause.set_translation(target_language, contents);
+            if !clause.is_machine_translated(target_language) {
+                clause
+                    .clause_machine_languages
+                    .push(target_language.to_string().into());
+            }
+        }
+        Ok(pending.len())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::clause_list;
+
+    fn render(inlines: ast::InlineNodeList<'_>) -> String {
+        let mut text = String::new();
+        crate::textualize::textualize_inline_list(&inlines, &mut text);
+        text
+    }
+
+    #[test]
+    fn placeholders_round_trip() {
+        let clause_list = clause_list("Run `cargo` in *the* [root](https://example.com).\n");
+        let (text, placeholders) = protect_placeholders(&clause_list.clauses[0].clause_contents);
+        assert_eq!(text, "Run ⟦1⟧ in ⟦2⟧the⟦/2⟧ ⟦3⟧root⟦/3⟧.");
+        let restored = restore_placeholders("⟦3⟧Racine⟦/3⟧ : ⟦2⟧la⟦/2⟧ ⟦1⟧.", &placeholders);
+        assert_eq!(render(restored), "(link) : (la) (code).");
+    }
+
+    #[test]
+    fn broken_placeholders_are_recovered() {
+        let clause_list = clause_list("Use *this*.\n");
+        let (_, placeholders) = protect_placeholders(&clause_list.clauses[0].clause_contents);
+        assert_eq!(
+            render(restore_placeholders("⟦2⟧ ⟦1⟧ceci.", &placeholders)),
+            "⟦2⟧ (ceci.)"
+        );
+    }
+
+    #[test]
+    fn brackets_in_the_source_are_not_placeholders() {
+        let clause_list = clause_list("Write ⟦1⟧ for *it*.\n");
+        let (text, placeholders) = protect_placeholders(&clause_list.clauses[0].clause_contents);
+        assert_eq!(text, "Write ⟦1⟧1⟦2⟧ for ⟦3⟧it⟦/3⟧.");
+        assert_eq!(
+            render(restore_placeholders(
+                "⟦3⟧Ça⟦/3⟧ s’écrit ⟦1⟧1⟦2⟧.",
+                &placeholders
+            )),
+            "(Ça) s’écrit ⟦1⟧."
+        );
+    }
+
+    struct ShortTranslator;
+
+    impl Translator for ShortTranslator {
+        fn translate(
+            &mut self,
+            _source_language: &str,
+            _target_language: &str,
+            units: &[TranslationUnit],
+        ) -> Result<Vec<String>, TranslatorError> {
+            Ok(units.iter().skip(1).map(|unit| unit.text.clone()).collect())
+        }
+    }
+
+    #[test]
+    fn prefill_marks_machine_translations() {
+        let mut clause_list = clause_list("One. Two.\n");
+        let count = clause_list
+            .prefill_translations(&mut MockTranslator, "en-US", "fr", 10)
+            .unwrap();
+        assert_eq!(count, 2);
+        assert!(clause_list.clauses[1].is_machine_translated("fr"));
+        assert_eq!(
+            render(clause_list.clauses[1].translation("fr").unwrap().to_vec()),
+            "[fr] Two."
+        );
+    }
+
+    #[test]
+    fn prefill_rejects_missing_translations() {
+        let mut clause_list = clause_list("One. Two.\n");
+        assert!(matches!(
+            clause_list.prefill_translations(&mut ShortTranslator, "en-US", "fr", 10),
+            Err(TranslatorError::TranslationCount {
+                expected: 2,
+                got: 1
+            })
+        ));
+        assert!(clause_list.clauses[0].translation("fr").is_none());
+    }
+
+    /// Translates the first batch it is given and fails on the next.
+    struct FlakyTranslator {
+        calls: usize,
+    }
+
+    impl Translator for FlakyTranslator {
+        fn translate(
+            &mut self,
+            source_language: &str,
+            target_language: &str,
+            units: &[TranslationUnit],
+        ) -> Result<Vec<String>, TranslatorError> {
+            self.calls += 1;
+            if self.calls > 1 {
+                return Err(TranslatorError::Backend("gone".into()));
+            }
+            MockTranslator.translate(source_language, target_language, units)
+        }
+    }
+
+    #[test]
+    fn prefill_changes_nothing_unless_every_batch_succeeds() {
+        let mut clause_list = clause_list("One. Two.\n");
+        let mut translator = FlakyTranslator { calls: 0 };
+        assert!(matches!(
+            clause_list.prefill_translations(&mut translator, "en-US", "fr", 1),
+            Err(TranslatorError::Backend(_))
+        ));
+        assert_eq!(translator.calls, 2);
+        assert!(clause_list.clauses[0].translation("fr").is_none());
+        assert!(!clause_list.clauses[0].is_machine_translated("fr"));
+    }
+}
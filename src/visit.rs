@@ -0,0 +1,399 @@
+//! Traversals of a `Document`, for writing passes over it outside of this crate.
+//!
+//! `Visitor` walks a document by reference, for as long as `'d`, so that it may keep
+//! references to the nodes it visits. `VisitorMut` walks it by mutable reference, while
+//! `Fold` consumes it and builds a new one. Each method defaults to visiting the children
+//! of its node through the matching `walk_*` or `fold_*_children` function, so an
+//! implementation only overrides the nodes it is interested in, calling that function
+//! itself to keep descending.
+use crate::ast;
+
+pub trait Visitor<'d, 'a> {
+    fn visit_document(&mut self, doc: &'d ast::Document<'a>) {
+        walk_document(self, doc)
+    }
+
+    fn visit_blocks(&mut self, blocks: &'d ast::BlockNodeList<'a>) {
+        for block in blocks {
+            self.visit_block(block);
+        }
+    }
+
+    fn visit_block(&mut self, block: &'d ast::BlockNode<'a>) {
+        walk_block(self, block)
+    }
+
+    fn visit_outofband(&mut self, outofband: &'d ast::OutOfBandNode<'a>) {
+        walk_outofband(self, outofband)
+    }
+
+    fn visit_inlines(&mut self, inlines: &'d ast::InlineNodeList<'a>) {
+        for inline in inlines {
+            self.visit_inline(inline);
+        }
+    }
+
+    fn visit_inline(&mut self, inline: &'d ast::InlineNode<'a>) {
+        walk_inline(self, inline)
+    }
+}
+
+pub fn walk_document<'d, 'a, V: Visitor<'d, 'a> + ?Sized>(
+    visitor: &mut V,
+    doc: &'d ast::Document<'a>,
+) {
+    visitor.visit_blocks(&doc.blocks);
+    for outofband in &doc.outofbands {
+        visitor.visit_outofband(outofband);
+    }
+}
+
+pub fn walk_block<'d, 'a, V: Visitor<'d, 'a> + ?Sized>(
+    visitor: &mut V,
+    block: &'d ast::BlockNode<'a>,
+) {
+    match block {
+        ast::BlockNode::Container { children, .. } => visitor.visit_blocks(children),
+        ast::BlockNode::Leaf { contents, .. } => visitor.visit_inlines(contents),
+        ast::BlockNode::Markup { .. } => {}
+    }
+}
+
+pub fn walk_outofband<'d, 'a, V: Visitor<'d, 'a> + ?Sized>(
+    visitor: &mut V,
+    outofband: &'d ast::OutOfBandNode<'a>,
+) {
+    match outofband {
+        ast::OutOfBandNode::OutOfBandContainer { children, .. } => visitor.visit_blocks(children),
+        ast::OutOfBandNode::OutOfBandLeaf { contents, .. } => visitor.visit_inlines(contents),
+    }
+}
+
+pub fn walk_inline<'d, 'a, V: Visitor<'d, 'a> + ?Sized>(
+    visitor: &mut V,
+    inline: &'d ast::InlineNode<'a>,
+) {
+    match inline {
+        ast::InlineNode::Surrounding { contents, .. } => visitor.visit_inlines(contents),
+        ast::InlineNode::Content { .. } => {}
+    }
+}
+
+pub trait VisitorMut<'a> {
+    fn visit_document_mut(&mut self, doc: &mut ast::Document<'a>) {
+        walk_document_mut(self, doc)
+    }
+
+    fn visit_blocks_mut(&mut self, blocks: &mut ast::BlockNodeList<'a>) {
+        for block in blocks.iter_mut() {
+            self.visit_block_mut(block);
+        }
+    }
+
+    fn visit_block_mut(&mut self, block: &mut ast::BlockNode<'a>) {
+        walk_block_mut(self, block)
+    }
+
+    fn visit_outofband_mut(&mut self, outofband: &mut ast::OutOfBandNode<'a>) {
+        walk_outofband_mut(self, outofband)
+    }
+
+    fn visit_inlines_mut(&mut self, inlines: &mut ast::InlineNodeList<'a>) {
+        for inline in inlines.iter_mut() {
+            self.visit_inline_mut(inline);
+        }
+    }
+
+    fn visit_inline_mut(&mut self, inline: &mut ast::InlineNode<'a>) {
+        walk_inline_mut(self, inline)
+    }
+}
+
+pub fn walk_document_mut<'a, V: VisitorMut<'a> + ?Sized>(
+    visitor: &mut V,
+    doc: &mut ast::Document<'a>,
+) {
+    visitor.visit_blocks_mut(&mut doc.blocks);
+    for outofband in doc.outofbands.iter_mut() {
+        visitor.visit_outofband_mut(outofband);
+    }
+}
+
+pub fn walk_block_mut<'a, V: VisitorMut<'a> + ?Sized>(
+    visitor: &mut V,
+    block: &mut ast::BlockNode<'a>,
+) {
+    match block {
+        ast::BlockNode::Container { children, .. } => visitor.visit_blocks_mut(children),
+        ast::BlockNode::Leaf { contents, .. } => visitor.visit_inlines_mut(contents),
+        ast::BlockNode::Markup { .. } => {}
+    }
+}
+
+pub fn walk_outofband_mut<'a, V: VisitorMut<'a> + ?Sized>(
+    visitor: &mut V,
+    outofband: &mut ast::OutOfBandNode<'a>,
+) {
+    match outofband {
+        ast::OutOfBandNode::OutOfBandContainer { children, .. } => {
+            visitor.visit_blocks_mut(children)
+        }
+        ast::OutOfBandNode::OutOfBandLeaf { contents, .. } => visitor.visit_inlines_mut(contents),
+    }
+}
+
+pub fn walk_inline_mut<'a, V: VisitorMut<'a> + ?Sized>(
+    visitor: &mut V,
+    inline: &mut ast::InlineNode<'a>,
+) {
+    match inline {
+        ast::InlineNode::Surrounding { contents, .. } => visitor.visit_inlines_mut(contents),
+        ast::InlineNode::Content { .. } => {}
+    }
+}
+
+/// A transformer rebuilding a document node by node. The list methods may also drop,
+/// insert or merge nodes.
+pub trait Fold<'a> {
+    fn fold_document(&mut self, doc: ast::Document<'a>) -> ast::Document<'a> {
+        fold_document_children(self, doc)
+    }
+
+    fn fold_blocks(&mut self, blocks: ast::BlockNodeList<'a>) -> ast::BlockNodeList<'a> {
+        blocks
+            .into_iter()
+            .map(|block| self.fold_block(block))
+            .collect()
+    }
+
+    fn fold_block(&mut self, block: ast::BlockNode<'a>) -> ast::BlockNode<'a> {
+        fold_block_children(self, block)
+    }
+
+    fn fold_outofband(&mut self, outofband: ast::OutOfBandNode<'a>) -> ast::OutOfBandNode<'a> {
+        fold_outofband_children(self, outofband)
+    }
+
+    fn fold_inlines(&mut self, inlines: ast::InlineNodeList<'a>) -> ast::InlineNodeList<'a> {
+        inlines
+            .into_iter()
+            .map(|inline| self.fold_inline(inline))
+            .collect()
+    }
+
+    fn fold_inline(&mut self, inline: ast::InlineNode<'a>) -> ast::InlineNode<'a> {
+        fold_inline_children(self, inline)
+    }
+}
+
+pub fn fold_document_children<'a, F: Fold<'a> + ?Sized>(
+    folder: &mut F,
+    doc: ast::Document<'a>,
+) -> ast::Document<'a> {
+    ast::Document {
+        blocks: folder.fold_blocks(doc.blocks),
+        outofbands: doc
+            .outofbands
+            .into_iter()
+            .map(|outofband| folder.fold_outofband(outofband))
+            .collect(),
+    }
+}
+
+pub fn fold_block_children<'a, F: Fold<'a> + ?Sized>(
+    folder: &mut F,
+    block: ast::BlockNode<'a>,
+) -> ast::BlockNode<'a> {
+    match block {
+        ast::BlockNode::Container {
+            tag,
+            children,
+            span,
+        } => ast::BlockNode::Container {
+            tag,
+            children: folder.fold_blocks(children),
+            span,
+        },
+        ast::BlockNode::Leaf {
+            tag,
+            contents,
+            span,
+        } => ast::BlockNode::Leaf {
+            tag,
+            contents: folder.fold_inlines(contents),
+            span,
+        },
+        block @ ast::BlockNode::Markup { .. } => block,
+    }
+}
+
+pub fn fold_outofband_children<'a, F: Fold<'a> + ?Sized>(
+    folder: &mut F,
+    outofband: ast::OutOfBandNode<'a>,
+) -> ast::OutOfBandNode<'a> {
+    match outofband {
+        ast::OutOfBandNode::OutOfBandContainer {
+            tag,
+            children,
+            span,
+        } => ast::OutOfBandNode::OutOfBandContainer {
+            tag,
+            children: folder.fold_blocks(children),
+            span,
+        },
+        ast::OutOfBandNode::OutOfBandLeaf {
+            tag,
+            contents,
+            span,
+        } => ast::OutOfBandNode::OutOfBandLeaf {
+            tag,
+            contents: folder.fold_inlines(contents),
+            span,
+        },
+    }
+}
+
+pub fn fold_inline_children<'a, F: Fold<'a> + ?Sized>(
+    folder: &mut F,
+    inline: ast::InlineNode<'a>,
+) -> ast::InlineNode<'a> {
+    match inline {
+        ast::InlineNode::Surrounding {
+            tag,
+            contents,
+            span,
+        } => ast::InlineNode::Surrounding {
+            tag,
+            contents: folder.fold_inlines(contents),
+            span,
+        },
+        inline @ ast::InlineNode::Content { .. } => inline,
+    }
+}
+
+impl<'a> ast::Document<'a> {
+    /// Calls `visitor` on the document, see `Visitor`.
+    pub fn accept<'d, V: Visitor<'d, 'a> + ?Sized>(&'d self, visitor: &mut V) {
+        visitor.visit_document(self)
+    }
+
+    /// Calls `visitor` on the document, see `VisitorMut`.
+    pub fn accept_mut<V: VisitorMut<'a> + ?Sized>(&mut self, visitor: &mut V) {
+        visitor.visit_document_mut(self)
+    }
+
+    /// Rebuilds the document through `folder`, see `Fold`.
+    pub fn fold<F: Fold<'a> + ?Sized>(self, folder: &mut F) -> Self {
+        folder.fold_document(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::parse;
+
+    /// Keeps a reference to the text of each heading.
+    struct Headings<'d, 'a> {
+        headings: Vec<&'d ast::InlineNodeList<'a>>,
+    }
+
+    impl<'d, 'a> Visitor<'d, 'a> for Headings<'d, 'a> {
+        fn visit_block(&mut self, block: &'d ast::BlockNode<'a>) {
+            match block {
+                ast::BlockNode::Leaf {
+                    tag: ast::LeafBlockTag::Heading(_),
+                    contents,
+                    ..
+                } => self.headings.push(contents),
+                block => walk_block(self, block),
+            }
+        }
+    }
+
+    /// Upper-cases every piece of text.
+    struct Shout;
+
+    impl<'a> VisitorMut<'a> for Shout {
+        fn visit_inline_mut(&mut self, inline: &mut ast::InlineNode<'a>) {
+            match inline {
+                ast::InlineNode::Content {
+                    tag: ast::ContentInlineTag::Text(s),
+                    ..
+                } => *s = s.to_uppercase().into(),
+                inline => walk_inline_mut(self, inline),
+            }
+        }
+    }
+
+    /// Replaces each emphasis with its contents.
+    struct Unemphasize;
+
+    impl<'a> Fold<'a> for Unemphasize {
+        fn fold_inlines(&mut self, inlines: ast::InlineNodeList<'a>) -> ast::InlineNodeList<'a> {
+            let mut output = Vec::new();
+            for inline in inlines {
+                match inline {
+                    ast::InlineNode::Surrounding {
+                        tag: ast::SurroundingInlineTag::Emphasis,
+                        contents,
+                        ..
+                    } => output.extend(self.fold_inlines(contents)),
+                    inline => output.push(self.fold_inline(inline)),
+                }
+            }
+            output
+        }
+    }
+
+    fn text(inlines: &ast::InlineNodeList<'_>) -> String {
+        inlines
+            .iter()
+            .map(|inline| match inline {
+                ast::InlineNode::Content {
+                    tag: ast::ContentInlineTag::Text(s),
+                    ..
+                } => s.to_string(),
+                ast::InlineNode::Surrounding { contents, .. } => text(contents),
+                ast::InlineNode::Content { .. } => String::new(),
+            })
+            .collect()
+    }
+
+    fn render(doc: ast::Document<'_>) -> String {
+        let mut output = String::new();
+        pulldown_cmark_to_cmark::cmark(doc.into_tokens(), &mut output, None).unwrap();
+        output
+    }
+
+    #[test]
+    fn visitors_keep_references() {
+        let doc = parse("# One\n\n> ## *Two*\n\nText.\n");
+        let mut visitor = Headings {
+            headings: Vec::new(),
+        };
+        doc.accept(&mut visitor);
+        let texts: Vec<String> = visitor
+            .headings
+            .iter()
+            .map(|contents| text(contents))
+            .collect();
+        assert_eq!(texts, vec!["One", "Two"]);
+    }
+
+    #[test]
+    fn mutable_visitors_reach_nested_inlines() {
+        let mut doc = parse("- Some *nested* text\n");
+        doc.accept_mut(&mut Shout);
+        assert_eq!(render(doc), "* SOME *NESTED* TEXT");
+    }
+
+    #[test]
+    fn folds_can_replace_nodes_with_their_children() {
+        let doc = parse("> Some *nested **strong*** text\n");
+        assert_eq!(
+            render(doc.fold(&mut Unemphasize)),
+            "\n > \n > Some nested **strong** text"
+        );
+    }
+}